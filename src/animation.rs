@@ -0,0 +1,565 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use legion::system;
+use nalgebra::{Quaternion, UnitQuaternion, Vector3, Vector4};
+use petgraph::stable_graph::NodeIndex;
+
+use crate::scene::{self, ImportError, Node, Scene};
+
+/// Imports the animations of a glTF document, their channels target the nodes of the scene
+/// imported from the same document.
+pub(crate) fn import(
+    doc: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<Arc<AnimationClip>>, ImportError> {
+    doc.animations()
+        .map(|animation| {
+            let channels = animation
+                .channels()
+                .enumerate()
+                .map(|(index, channel)| {
+                    let malformed = |accessor| ImportError::MalformedAccessor {
+                        location: format!(
+                            "animation {} channel {index}",
+                            scene::label(animation.name(), animation.index()),
+                        ),
+                        accessor,
+                    };
+                    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let times = reader
+                        .read_inputs()
+                        .ok_or_else(|| malformed("input"))?
+                        .collect::<Vec<_>>();
+                    let outputs = reader.read_outputs().ok_or_else(|| malformed("output"))?;
+                    let (property, values) = match outputs {
+                        gltf::animation::util::ReadOutputs::Translations(translations) => (
+                            Property::Translation,
+                            translations.flatten().collect::<Vec<_>>(),
+                        ),
+                        gltf::animation::util::ReadOutputs::Rotations(rotations) => {
+                            (Property::Rotation, rotations.into_f32().flatten().collect())
+                        }
+                        gltf::animation::util::ReadOutputs::Scales(scales) => {
+                            (Property::Scale, scales.flatten().collect())
+                        }
+                        gltf::animation::util::ReadOutputs::MorphTargetWeights(weights) => {
+                            (Property::Weights, weights.into_f32().collect())
+                        }
+                    };
+                    let interpolation = match channel.sampler().interpolation() {
+                        gltf::animation::Interpolation::Step => Interpolation::Step,
+                        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                    };
+
+                    let elements = if interpolation == Interpolation::CubicSpline {
+                        3
+                    } else {
+                        1
+                    };
+                    if times.is_empty() || values.len() % (times.len() * elements) != 0 {
+                        return Err(malformed("output"));
+                    }
+
+                    Ok(Channel {
+                        target: NodeIndex::new(channel.target().node().index()),
+                        property,
+                        interpolation,
+                        times,
+                        values,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let duration = channels
+                .iter()
+                .filter_map(|channel| channel.times.last().copied())
+                .fold(0.0, f32::max);
+
+            Ok(Arc::new(AnimationClip {
+                name: animation.name().map(String::from),
+                duration,
+                channels,
+            }))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    Weights,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+/// Keyframes of one property of a node.
+pub struct Channel {
+    pub target: NodeIndex,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    /// Keyframe values one after the other, cubic splines store an in-tangent, the value and an
+    /// out-tangent for every keyframe.
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    /// Interpolates the value at `time`, clamped to the first and last keyframes.
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let elements = if self.interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        let width = self.values.len() / (self.times.len() * elements).max(1);
+        // Element 0 is the in-tangent, 1 the value and 2 the out-tangent of cubic splines.
+        let element = |keyframe: usize, element: usize| {
+            let offset = (keyframe * elements + element.min(elements - 1)) * width;
+            &self.values[offset..offset + width]
+        };
+
+        let next = self.times.partition_point(|&keyframe| keyframe <= time);
+        if next == 0 {
+            return element(0, 1).to_vec();
+        }
+        if next == self.times.len() {
+            return element(next - 1, 1).to_vec();
+        }
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = (time - self.times[previous]) / delta;
+
+        let value = match self.interpolation {
+            Interpolation::Step => element(previous, 1).to_vec(),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let [from, to] = [previous, next].map(|keyframe| rotation(element(keyframe, 1)));
+                from.slerp(&to, t).coords.as_slice().to_vec()
+            }
+            Interpolation::Linear => element(previous, 1)
+                .iter()
+                .zip(element(next, 1))
+                .map(|(from, to)| from + (to - from) * t)
+                .collect(),
+            Interpolation::CubicSpline => {
+                let (t2, t3) = (t * t, t * t * t);
+                let from = element(previous, 1).iter().zip(element(previous, 2));
+                let to = element(next, 1).iter().zip(element(next, 0));
+                from.zip(to)
+                    .map(|((from, out_tangent), (to, in_tangent))| {
+                        (2.0 * t3 - 3.0 * t2 + 1.0) * from
+                            + (t3 - 2.0 * t2 + t) * delta * out_tangent
+                            + (-2.0 * t3 + 3.0 * t2) * to
+                            + (t3 - t2) * delta * in_tangent
+                    })
+                    .collect()
+            }
+        };
+        if self.property == Property::Rotation {
+            return rotation(value.as_slice()).coords.as_slice().to_vec();
+        }
+        value
+    }
+}
+
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Time of the last keyframe in seconds.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    /// Poses of the nodes the clip animates at `time`, properties it leaves alone keep their rest
+    /// values.
+    pub fn sample(&self, scene: &Scene, time: f32) -> HashMap<NodeIndex, Pose> {
+        let mut poses = HashMap::new();
+        self.channels
+            .iter()
+            .filter(|channel| scene.contains_node(channel.target))
+            .for_each(|channel| {
+                let pose = poses
+                    .entry(channel.target)
+                    .or_insert_with(|| scene[channel.target].rest_pose().clone());
+                let value = channel.sample(time);
+                match channel.property {
+                    Property::Translation => pose.translation = Vector3::from_column_slice(&value),
+                    Property::Rotation => pose.rotation = rotation(&value),
+                    Property::Scale => pose.scale = Vector3::from_column_slice(&value),
+                    Property::Weights => pose.weights = value,
+                }
+            });
+        poses
+    }
+}
+
+/// Transform and morph target weights of a node as sampled from a clip.
+#[derive(Debug, Clone)]
+pub struct Pose {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+    pub weights: Vec<f32>,
+}
+
+impl Pose {
+    #[must_use]
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let weights = if self.weights.len() == other.weights.len() {
+            self.weights
+                .iter()
+                .zip(other.weights.iter())
+                .map(|(from, to)| from + (to - from) * t)
+                .collect()
+        } else {
+            other.weights.clone()
+        };
+
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+            weights,
+        }
+    }
+}
+
+impl From<&Node> for Pose {
+    fn from(node: &Node) -> Self {
+        Self {
+            translation: *node.translation(),
+            rotation: *node.rotation(),
+            scale: *node.scale(),
+            weights: node.weights.clone(),
+        }
+    }
+}
+
+/// A clip being played, its time advances by the frame duration times the speed and either
+/// wraps around or stops at the ends.
+#[derive(Clone)]
+pub struct Playback {
+    pub clip: Arc<AnimationClip>,
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl Playback {
+    pub fn new(clip: Arc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub const fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub const fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping
+            && ((self.speed > 0.0 && self.time >= self.clip.duration)
+                || (self.speed < 0.0 && self.time <= 0.0))
+    }
+
+    fn advance(&mut self, delta_time: f32) {
+        self.time += delta_time * self.speed;
+        self.time = if self.looping && self.clip.duration > 0.0 {
+            self.time.rem_euclid(self.clip.duration)
+        } else {
+            self.time.clamp(0.0, self.clip.duration)
+        };
+    }
+}
+
+/// Animates the `Scene` of its entity, switching clips can cross fade from the previous one.
+pub struct AnimationPlayer {
+    pub current: Option<Playback>,
+    pub previous: Option<Playback>,
+    /// Weight of the current clip over the previous one, it reaches 1 once the fade is over.
+    pub blend: f32,
+    pub fade_duration: Duration,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            current: None,
+            previous: None,
+            blend: 1.0,
+            fade_duration: Duration::ZERO,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new(playback: Playback) -> Self {
+        Self {
+            current: Some(playback),
+            ..Default::default()
+        }
+    }
+
+    pub fn play(&mut self, playback: Playback) {
+        *self = Self::new(playback);
+    }
+
+    /// Plays a clip blending it in over the previous one during `fade_duration`.
+    pub fn cross_fade(&mut self, playback: Playback, fade_duration: Duration) {
+        self.previous = self.current.replace(playback);
+        self.blend = 0.0;
+        self.fade_duration = fade_duration;
+    }
+
+    pub fn advance(&mut self, delta_time: Duration) {
+        let delta_time = delta_time.as_secs_f32();
+        self.current
+            .iter_mut()
+            .chain(self.previous.iter_mut())
+            .for_each(|playback| playback.advance(delta_time));
+        if self.previous.is_some() {
+            self.blend = if self.fade_duration.is_zero() {
+                1.0
+            } else {
+                (self.blend + delta_time / self.fade_duration.as_secs_f32()).min(1.0)
+            };
+        }
+        if self.blend >= 1.0 {
+            self.previous = None;
+        }
+    }
+
+    /// Writes the blended poses of the playing clips to the nodes of a scene.
+    pub fn apply(&self, scene: &mut Scene) {
+        let Some(current) = &self.current else {
+            return;
+        };
+        let mut poses = current.clip.sample(scene, current.time);
+        if let Some(previous) = &self.previous {
+            let mut previous_poses = previous.clip.sample(scene, previous.time);
+            // Nodes only one of the clips animates fade from or to their rest pose.
+            previous_poses.keys().for_each(|&index| {
+                poses
+                    .entry(index)
+                    .or_insert_with(|| scene[index].rest_pose().clone());
+            });
+            poses.iter_mut().for_each(|(index, pose)| {
+                let from = previous_poses
+                    .remove(index)
+                    .unwrap_or_else(|| scene[*index].rest_pose().clone());
+                *pose = from.lerp(pose, self.blend);
+            });
+        }
+
+        poses.into_iter().for_each(|(index, pose)| {
+            let node = &mut scene[index];
+            node.set_translation(pose.translation);
+            node.set_rotation(pose.rotation);
+            node.set_scale(pose.scale);
+            node.weights = pose.weights;
+        });
+    }
+}
+
+#[system(for_each)]
+pub fn animate(player: &mut AnimationPlayer, scene: &mut Scene, #[resource] delta_time: &Duration) {
+    player.advance(*delta_time);
+    player.apply(scene);
+}
+
+fn rotation(value: &[f32]) -> UnitQuaternion<f32> {
+    UnitQuaternion::from_quaternion(Quaternion::from(Vector4::from_column_slice(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Node;
+
+    fn channel(
+        property: Property,
+        interpolation: Interpolation,
+        times: &[f32],
+        values: &[f32],
+    ) -> Channel {
+        Channel {
+            target: NodeIndex::new(0),
+            property,
+            interpolation,
+            times: times.to_vec(),
+            values: values.to_vec(),
+        }
+    }
+
+    fn clip(duration: f32, channels: Vec<Channel>) -> Arc<AnimationClip> {
+        Arc::new(AnimationClip {
+            name: None,
+            duration,
+            channels,
+        })
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        actual.iter().zip(expected).for_each(|(actual, expected)| {
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "{actual:?} != {expected:?}"
+            );
+        });
+    }
+
+    #[test]
+    fn step_holds_the_previous_keyframe() {
+        let channel = channel(
+            Property::Translation,
+            Interpolation::Step,
+            &[0.0, 1.0],
+            &[0.0, 0.0, 0.0, 4.0, 2.0, 1.0],
+        );
+        assert_close(&channel.sample(0.99), &[0.0, 0.0, 0.0]);
+        assert_close(&channel.sample(1.0), &[4.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn linear_interpolates_between_keyframes() {
+        let channel = channel(
+            Property::Translation,
+            Interpolation::Linear,
+            &[1.0, 3.0],
+            &[0.0, 0.0, 0.0, 4.0, 2.0, 1.0],
+        );
+        assert_close(&channel.sample(1.5), &[1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn linear_rotations_slerp() {
+        let turn = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 1.0);
+        let channel = channel(
+            Property::Rotation,
+            Interpolation::Linear,
+            &[0.0, 1.0],
+            &[[0.0, 0.0, 0.0, 1.0].as_slice(), turn.coords.as_slice()].concat(),
+        );
+        let half_turn = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.5);
+        assert_close(&channel.sample(0.5), half_turn.coords.as_slice());
+    }
+
+    #[test]
+    fn cubic_spline_tangents_scale_with_the_keyframe_delta() {
+        // In-tangent, value and out-tangent of two keyframes two seconds apart.
+        let channel = channel(
+            Property::Weights,
+            Interpolation::CubicSpline,
+            &[0.0, 2.0],
+            &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+        );
+        // (t³ - 2t² + t) × delta × out-tangent at t = 0.5.
+        assert_close(&channel.sample(1.0), &[0.25]);
+    }
+
+    #[test]
+    fn sampling_clamps_outside_the_keyframes() {
+        let channel = channel(
+            Property::Weights,
+            Interpolation::CubicSpline,
+            &[1.0, 2.0],
+            &[9.0, 1.0, 9.0, 9.0, 3.0, 9.0],
+        );
+        assert_close(&channel.sample(0.0), &[1.0]);
+        assert_close(&channel.sample(5.0), &[3.0]);
+    }
+
+    #[test]
+    fn looping_playback_wraps_in_both_directions() {
+        let mut playback = Playback::new(clip(2.0, Vec::new()));
+        playback.time = 1.5;
+        playback.advance(1.0);
+        assert_close(&[playback.time], &[0.5]);
+
+        let mut playback = playback.with_speed(-1.0);
+        playback.advance(1.0);
+        assert_close(&[playback.time], &[1.5]);
+        assert!(!playback.is_finished());
+    }
+
+    #[test]
+    fn playback_without_looping_stops_at_the_ends() {
+        let mut playback = Playback::new(clip(2.0, Vec::new())).with_looping(false);
+        playback.advance(3.0);
+        assert_close(&[playback.time], &[2.0]);
+        assert!(playback.is_finished());
+
+        let mut playback = playback.with_speed(-2.0);
+        playback.advance(3.0);
+        assert_close(&[playback.time], &[0.0]);
+        assert!(playback.is_finished());
+    }
+
+    #[test]
+    fn cross_fade_ramps_the_blend_and_drops_the_finished_clip() {
+        let mut player = AnimationPlayer::new(Playback::new(clip(1.0, Vec::new())));
+        player.cross_fade(Playback::new(clip(1.0, Vec::new())), Duration::from_secs(1));
+        assert_close(&[player.blend], &[0.0]);
+
+        player.advance(Duration::from_millis(250));
+        assert_close(&[player.blend], &[0.25]);
+        assert!(player.previous.is_some());
+
+        player.advance(Duration::from_secs(1));
+        assert_close(&[player.blend], &[1.0]);
+        assert!(player.previous.is_none());
+    }
+
+    #[test]
+    fn nodes_left_by_the_previous_clip_fade_to_their_rest_pose() {
+        // A node some earlier animation moved away from its rest pose at the origin.
+        let mut scene = Scene::default();
+        let index = scene.add_node(Node::default().with_translation(Vector3::new(7.0, 0.0, 0.0)));
+        let other = scene.add_node(Node::default());
+
+        let previous = clip(
+            1.0,
+            vec![channel(
+                Property::Translation,
+                Interpolation::Step,
+                &[0.0],
+                &[10.0, 0.0, 0.0],
+            )],
+        );
+        let mut current = channel(
+            Property::Translation,
+            Interpolation::Step,
+            &[0.0],
+            &[0.0, 1.0, 0.0],
+        );
+        current.target = other;
+        let mut player = AnimationPlayer::new(Playback::new(previous));
+        player.cross_fade(
+            Playback::new(clip(1.0, vec![current])),
+            Duration::from_secs(1),
+        );
+        player.advance(Duration::from_millis(500));
+
+        // Applying twice must not blend from the pose written by the first call.
+        player.apply(&mut scene);
+        player.apply(&mut scene);
+        assert_close(scene[index].translation().as_slice(), &[5.0, 0.0, 0.0]);
+        assert_close(scene[other].translation().as_slice(), &[0.0, 0.5, 0.0]);
+    }
+}
@@ -1,26 +1,123 @@
-use nalgebra::{Matrix4, Perspective3, Point3, Rotation3, Translation3, Vector3};
+use nalgebra::{Matrix4, Point3, Rotation3, Translation3, Vector3};
 
-pub struct Projection {
-    aspect: f32,
-    fovy: f32,
-    znear: f32,
-    zfar: f32,
+/// Camera projections, all of them map depth to wgpu's 0..1 range.
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    Perspective {
+        /// Width over height, the viewport's one when `None`.
+        aspect: Option<f32>,
+        /// Vertical field of view in radians.
+        fovy: f32,
+        znear: f32,
+        /// Far clip plane, at infinity when `None`.
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        /// Half the width of the view volume.
+        xmag: f32,
+        /// Half the height of the view volume.
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+    /// Perspective with the far plane at infinity and depth reversed, 1 at the near plane and
+    /// approaching 0 at infinity, which spreads the depth precision evenly.
+    InfiniteReverseZ {
+        /// Width over height, the viewport's one when `None`.
+        aspect: Option<f32>,
+        /// Vertical field of view in radians.
+        fovy: f32,
+        znear: f32,
+    },
 }
 
 impl Projection {
     pub fn new(width: u32, height: u32) -> Self {
-        Self {
-            aspect: width as f32 / height as f32,
-            fovy: 45.0,
+        Self::Perspective {
+            aspect: Some(width as f32 / height as f32),
+            fovy: std::f32::consts::FRAC_PI_4,
             znear: 0.1,
-            zfar: 100.0,
+            zfar: Some(100.0),
+        }
+    }
+
+    /// Fills in the aspect ratio of perspective projections that leave it to the viewport.
+    pub fn with_viewport(mut self, width: u32, height: u32) -> Self {
+        if let Self::Perspective { aspect, .. } | Self::InfiniteReverseZ { aspect, .. } = &mut self
+        {
+            aspect.get_or_insert(width as f32 / height as f32);
         }
+        self
+    }
+
+    /// Whether depth decreases with the distance, the depth test has to pass greater values.
+    pub const fn is_reverse_z(&self) -> bool {
+        matches!(self, Self::InfiniteReverseZ { .. })
     }
 
     pub fn as_matrix(&self) -> Matrix4<f32> {
-        Perspective3::new(self.aspect, self.fovy, self.znear, self.zfar)
-            .as_matrix()
-            .to_owned()
+        match *self {
+            Self::Perspective {
+                aspect,
+                fovy,
+                znear,
+                zfar,
+            } => {
+                let (m22, m23) = zfar.map_or((-1.0, -znear), |zfar| {
+                    (zfar / (znear - zfar), znear * zfar / (znear - zfar))
+                });
+                Self::perspective_matrix(aspect, fovy, m22, m23)
+            }
+            Self::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => {
+                let mut matrix = Matrix4::identity();
+                matrix[(0, 0)] = 1.0 / xmag;
+                matrix[(1, 1)] = 1.0 / ymag;
+                matrix[(2, 2)] = 1.0 / (znear - zfar);
+                matrix[(2, 3)] = znear / (znear - zfar);
+                matrix
+            }
+            Self::InfiniteReverseZ {
+                aspect,
+                fovy,
+                znear,
+            } => Self::perspective_matrix(aspect, fovy, 0.0, znear),
+        }
+    }
+
+    /// Right handed perspective looking down -Z, `m22` and `m23` map the view depth to 0..1.
+    fn perspective_matrix(aspect: Option<f32>, fovy: f32, m22: f32, m23: f32) -> Matrix4<f32> {
+        let f = 1.0 / (fovy / 2.0).tan();
+        let mut matrix = Matrix4::zeros();
+        matrix[(0, 0)] = f / aspect.unwrap_or(1.0);
+        matrix[(1, 1)] = f;
+        matrix[(2, 2)] = m22;
+        matrix[(2, 3)] = m23;
+        matrix[(3, 2)] = -1.0;
+        matrix
+    }
+}
+
+impl From<gltf::camera::Projection<'_>> for Projection {
+    fn from(projection: gltf::camera::Projection<'_>) -> Self {
+        match projection {
+            gltf::camera::Projection::Perspective(perspective) => Self::Perspective {
+                aspect: perspective.aspect_ratio(),
+                fovy: perspective.yfov(),
+                znear: perspective.znear(),
+                zfar: perspective.zfar(),
+            },
+            gltf::camera::Projection::Orthographic(orthographic) => Self::Orthographic {
+                xmag: orthographic.xmag(),
+                ymag: orthographic.ymag(),
+                znear: orthographic.znear(),
+                zfar: orthographic.zfar(),
+            },
+        }
     }
 }
 
@@ -32,6 +129,19 @@ pub struct View {
 }
 
 impl View {
+    /// Places the view at a camera's world transform looking down its -Z axis, any roll is lost.
+    pub fn from_transform(transform_matrix: &Matrix4<f32>) -> Self {
+        let forward = (transform_matrix * -Vector3::z().to_homogeneous())
+            .xyz()
+            .normalize();
+
+        Self {
+            position: Point3::from(transform_matrix.column(3).xyz()),
+            yaw: forward.x.atan2(-forward.z),
+            pitch: -forward.y.asin(),
+        }
+    }
+
     pub fn as_matrix(&self) -> Matrix4<f32> {
         let translation = Translation3::from(-self.position);
         let rotation = Rotation3::from_axis_angle(&Vector3::x_axis(), self.pitch)
@@ -40,3 +150,107 @@ impl View {
         rotation.to_homogeneous() * translation.to_homogeneous()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        actual.iter().zip(expected).for_each(|(actual, expected)| {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{actual:?} != {expected:?}"
+            );
+        });
+    }
+
+    /// Normalized device coordinates of a point in view space.
+    fn project(projection: &Projection, x: f32, y: f32, z: f32) -> [f32; 3] {
+        let ndc = projection
+            .as_matrix()
+            .transform_point(&Point3::new(x, y, z));
+        [ndc.x, ndc.y, ndc.z]
+    }
+
+    #[test]
+    fn perspective_maps_the_clip_planes_to_zero_and_one() {
+        let projection = Projection::Perspective {
+            aspect: Some(2.0),
+            fovy: std::f32::consts::FRAC_PI_2,
+            znear: 0.5,
+            zfar: Some(10.0),
+        };
+        assert_close(&project(&projection, 0.0, 0.0, -0.5), &[0.0, 0.0, 0.0]);
+        assert_close(&project(&projection, 0.0, 0.0, -10.0), &[0.0, 0.0, 1.0]);
+        // A 90° field of view reaches the top edge at a slope of 1, the side at the aspect.
+        assert_close(&project(&projection, 4.0, 2.0, -2.0)[..2], &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn infinite_perspectives_approach_the_far_end_of_the_depth_range() {
+        let perspective = Projection::Perspective {
+            aspect: None,
+            fovy: 1.0,
+            znear: 0.1,
+            zfar: None,
+        };
+        assert_close(&project(&perspective, 0.0, 0.0, -0.1)[2..], &[0.0]);
+        assert_close(&project(&perspective, 0.0, 0.0, -1e5)[2..], &[1.0]);
+
+        let reverse_z = Projection::InfiniteReverseZ {
+            aspect: None,
+            fovy: 1.0,
+            znear: 0.1,
+        };
+        assert!(reverse_z.is_reverse_z());
+        assert_close(&project(&reverse_z, 0.0, 0.0, -0.1)[2..], &[1.0]);
+        assert_close(&project(&reverse_z, 0.0, 0.0, -1e5)[2..], &[0.0]);
+    }
+
+    #[test]
+    fn orthographic_maps_the_view_volume_to_the_clip_volume() {
+        let projection = Projection::Orthographic {
+            xmag: 4.0,
+            ymag: 2.0,
+            znear: 1.0,
+            zfar: 5.0,
+        };
+        assert_close(&project(&projection, 4.0, -2.0, -1.0), &[1.0, -1.0, 0.0]);
+        assert_close(&project(&projection, -2.0, 1.0, -5.0), &[-0.5, 0.5, 1.0]);
+        assert!(!projection.is_reverse_z());
+    }
+
+    #[test]
+    fn viewport_aspect_only_fills_in_missing_ones() {
+        let aspect = |projection: Projection| match projection.with_viewport(300, 100) {
+            Projection::Perspective { aspect, .. }
+            | Projection::InfiniteReverseZ { aspect, .. } => aspect,
+            Projection::Orthographic { .. } => None,
+        };
+        let perspective = |aspect| Projection::Perspective {
+            aspect,
+            fovy: 1.0,
+            znear: 0.1,
+            zfar: None,
+        };
+        assert_eq!(aspect(perspective(None)), Some(3.0));
+        assert_eq!(aspect(perspective(Some(0.5))), Some(0.5));
+    }
+
+    #[test]
+    fn views_from_camera_transforms_invert_them() {
+        let transform = Translation3::new(1.0, 2.0, 3.0).to_homogeneous()
+            * Rotation3::from_axis_angle(&Vector3::y_axis(), 0.5).to_homogeneous()
+            * Rotation3::from_axis_angle(&Vector3::x_axis(), -0.25).to_homogeneous();
+        let view = View::from_transform(&transform);
+        assert_close(view.position.coords.as_slice(), &[1.0, 2.0, 3.0]);
+        assert_close(&[view.yaw, view.pitch], &[-0.5, 0.25]);
+        assert_close(
+            (view.as_matrix() * transform).as_slice(),
+            Matrix4::identity().as_slice(),
+        );
+    }
+}
@@ -1,11 +1,13 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
+use legion::{world::SubWorld, IntoQuery};
 use nalgebra::Matrix4;
-use petgraph::visit::Dfs;
 
 use crate::{
+    camera::{Projection, View},
+    graph::{Attachment, Pass},
     renderer::{RenderNode, RenderNodeBuilder},
-    scene::Scene,
+    scene::{self, MaterialRegistry, Mesh, MeshInstance},
 };
 
 #[repr(C)]
@@ -15,11 +17,17 @@ pub struct VertexAttribute {
     pub normal: [f32; 3],
     pub color_0: [f32; 4],
     pub tex_coord_0: [f32; 2],
+    pub tangent: [f32; 4],
+    pub joints_0: [u32; 4],
+    pub weights_0: [f32; 4],
+    pub tex_coord_1: [f32; 2],
 }
 
 impl VertexAttribute {
-    const ATTRIBS: [wgpu::VertexAttribute; 4] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2];
+    const ATTRIBS: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2, 4 => Float32x4,
+        5 => Uint32x4, 6 => Float32x4, 7 => Float32x2
+    ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -38,20 +46,263 @@ impl Default for VertexAttribute {
             normal: Default::default(),
             color_0: [1.0; 4],
             tex_coord_0: Default::default(),
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            joints_0: Default::default(),
+            weights_0: Default::default(),
+            tex_coord_1: Default::default(),
         }
     }
 }
 
+/// Joint matrices and morph target weights of the meshes drawn in a frame, packed in two storage
+/// buffers every draw binds at its own dynamic offsets. Meshes without a skin get an identity
+/// palette. The renderer updates them once per frame for every pass drawing meshes.
+pub struct Deformations {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    buffers: RwLock<Option<DeformationBuffers>>,
+}
+
+/// Storage buffers of the last update, kept across frames and reallocated when they run out of
+/// room.
+struct DeformationBuffers {
+    joints: wgpu::Buffer,
+    weights: wgpu::Buffer,
+    /// Number of joint matrices and morph weights every draw binds.
+    binding_lens: [usize; 2],
+    bindings: DeformationBindings,
+}
+
+/// Bind group and per-mesh offsets of a deformation update, the passes keep a copy of the one
+/// of the current frame to draw with.
+#[derive(Clone, Default)]
+pub struct DeformationBindings {
+    bind_group: Option<Arc<wgpu::BindGroup>>,
+    offsets: Vec<[u32; 2]>,
+}
+
+impl DeformationBindings {
+    /// Binds the joints and weights of the `instance`-th mesh given to the update.
+    pub fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, index: u32, instance: usize) {
+        if let Some(bind_group) = &self.bind_group {
+            render_pass.set_bind_group(index, bind_group, &self.offsets[instance]);
+        }
+    }
+}
+
+impl Deformations {
+    /// Stride between dynamic storage offsets, the default `min_storage_buffer_offset_alignment`.
+    const ALIGNMENT: usize = 256;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let entry = |binding, min_binding_size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(min_binding_size as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("deformations"),
+            entries: &[
+                entry(0, std::mem::size_of::<[[f32; 4]; 4]>()),
+                entry(1, std::mem::size_of::<f32>()),
+            ],
+        });
+
+        Self {
+            bind_group_layout,
+            buffers: RwLock::new(None),
+        }
+    }
+
+    pub fn update(&self, device: &wgpu::Device, queue: &wgpu::Queue, instances: &[MeshInstance]) {
+        let mut buffers = self.buffers.write().unwrap();
+        if instances.is_empty() {
+            if let Some(buffers) = buffers.as_mut() {
+                buffers.bindings.offsets.clear();
+            }
+            return;
+        }
+
+        let palettes = instances
+            .iter()
+            .map(|instance| {
+                if instance.joint_matrices.is_empty() {
+                    vec![<[[f32; 4]; 4]>::from(Matrix4::identity())]
+                } else {
+                    instance
+                        .joint_matrices
+                        .iter()
+                        .map(|&matrix| matrix.into())
+                        .collect()
+                }
+            })
+            .collect::<Vec<_>>();
+        let weights = instances
+            .iter()
+            .map(|instance| {
+                if instance.weights.is_empty() {
+                    vec![0.0]
+                } else {
+                    instance.weights.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+        // Bindings only grow, so that a bind group stays valid for the frames after a larger one.
+        let previous_lens = buffers
+            .as_ref()
+            .map_or([0; 2], |buffers| buffers.binding_lens);
+        let binding_lens = [
+            palettes
+                .iter()
+                .map(Vec::len)
+                .max()
+                .unwrap()
+                .max(previous_lens[0]),
+            weights
+                .iter()
+                .map(Vec::len)
+                .max()
+                .unwrap()
+                .max(previous_lens[1]),
+        ];
+        let (joints_contents, joints_offsets) = Self::pack(&palettes, binding_lens[0]);
+        let (weights_contents, weights_offsets) = Self::pack(&weights, binding_lens[1]);
+        let joints_bytes: &[u8] = bytemuck::cast_slice(joints_contents.as_slice());
+        let weights_bytes: &[u8] = bytemuck::cast_slice(weights_contents.as_slice());
+
+        let fits = buffers.as_ref().is_some_and(|buffers| {
+            buffers.binding_lens == binding_lens
+                && buffers.joints.size() >= joints_bytes.len() as u64
+                && buffers.weights.size() >= weights_bytes.len() as u64
+        });
+        if !fits {
+            *buffers = Some(self.create_buffers(
+                device,
+                binding_lens,
+                joints_bytes.len().next_power_of_two(),
+                weights_bytes.len().next_power_of_two(),
+            ));
+        }
+        let buffers = buffers.as_mut().unwrap();
+        queue.write_buffer(&buffers.joints, 0, joints_bytes);
+        queue.write_buffer(&buffers.weights, 0, weights_bytes);
+        buffers.bindings.offsets = joints_offsets
+            .into_iter()
+            .zip(weights_offsets)
+            .map(|(joints_offset, weights_offset)| [joints_offset, weights_offset])
+            .collect();
+    }
+
+    /// Bind group and offsets of the last update.
+    pub fn bindings(&self) -> DeformationBindings {
+        self.buffers
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|buffers| buffers.bindings.clone())
+            .unwrap_or_default()
+    }
+
+    fn create_buffers(
+        &self,
+        device: &wgpu::Device,
+        binding_lens: [usize; 2],
+        joints_size: usize,
+        weights_size: usize,
+    ) -> DeformationBuffers {
+        let buffer = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let joints = buffer("joints", joints_size);
+        let weights = buffer("morph weights", weights_size);
+        let entry = |binding, buffer, size| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size as u64),
+            }),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("deformations"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                entry(
+                    0,
+                    &joints,
+                    binding_lens[0] * std::mem::size_of::<[[f32; 4]; 4]>(),
+                ),
+                entry(1, &weights, binding_lens[1] * std::mem::size_of::<f32>()),
+            ],
+        });
+
+        DeformationBuffers {
+            joints,
+            weights,
+            binding_lens,
+            bindings: DeformationBindings {
+                bind_group: Some(Arc::new(bind_group)),
+                offsets: Vec::new(),
+            },
+        }
+    }
+
+    /// Concatenates non-empty arrays at aligned offsets, with room after the last one for a
+    /// binding of `binding_len` elements.
+    fn pack<T: bytemuck::Pod>(arrays: &[Vec<T>], binding_len: usize) -> (Vec<T>, Vec<u32>) {
+        let element_size = std::mem::size_of::<T>();
+        let stride = Self::ALIGNMENT / element_size;
+
+        let mut contents = Vec::<T>::new();
+        let offsets = arrays
+            .iter()
+            .map(|array| {
+                contents.resize(contents.len().next_multiple_of(stride), T::zeroed());
+                let offset = (contents.len() * element_size) as u32;
+                contents.extend_from_slice(array);
+                offset
+            })
+            .collect::<Vec<_>>();
+        let last_offset = *offsets.last().unwrap() as usize / element_size;
+        contents.resize(contents.len().max(last_offset + binding_len), T::zeroed());
+        (contents, offsets)
+    }
+}
+
 pub struct GeometryPass {
-    pub render_node: RenderNode,
     transform_buffers: Vec<wgpu::Buffer>,
+    transform_bind_group_layout: wgpu::BindGroupLayout,
     transform_bind_group: wgpu::BindGroup,
+    material_bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    deformations: Arc<Deformations>,
+    deformation_bindings: DeformationBindings,
+    morph_targets_bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    meshes: Vec<Arc<Mesh>>,
+    reverse_z: bool,
 }
 
 impl GeometryPass {
+    pub const POSITION: &'static str = "gbuffer_position";
+    pub const NORMAL: &'static str = "gbuffer_normal";
+    pub const BASE_COLOR: &'static str = "gbuffer_base_color";
+    pub const MATERIAL: &'static str = "gbuffer_material";
+    pub const EMISSIVE: &'static str = "gbuffer_emissive";
+    pub const DEPTH: &'static str = "gbuffer_depth";
+
     const GBUFFER_POSITION_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
     const GBUFFER_NORMAL_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
-    const GBUFFER_ALBEDO_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+    const GBUFFER_BASE_COLOR_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+    const GBUFFER_MATERIAL_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    const GBUFFER_EMISSIVE_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
     const GBUFFER_DEPTH_STENCIL_TEXTURE_FORMAT: wgpu::TextureFormat =
         wgpu::TextureFormat::Depth24Plus;
 
@@ -62,7 +313,15 @@ impl GeometryPass {
     const TRANSFORM_PROJECTION_MATRIX_IDX: usize = 4;
     const TRANSFORM_PROJECTION_INV_MATRIX_IDX: usize = 5;
 
-    pub fn new(device: &wgpu::Device, size: wgpu::Extent3d) -> Self {
+    /// Stride between the model matrices of the meshes, bound at dynamic offsets, the default
+    /// `min_uniform_buffer_offset_alignment`.
+    const MODEL_STRIDE: u64 = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        materials: &MaterialRegistry,
+        deformations: Arc<Deformations>,
+    ) -> Self {
         let mut transform_buffers = vec![];
         (0..6).for_each(|_| {
             let buffer = wgpu::util::DeviceExt::create_buffer_init(
@@ -76,93 +335,112 @@ impl GeometryPass {
             transform_buffers.push(buffer);
         });
 
+        // The model matrices change with every mesh, the camera ones are shared by all of them.
         let transform_bind_group_layout_entries = (0..6)
             .map(|index| wgpu::BindGroupLayoutEntry {
                 binding: index,
                 visibility: wgpu::ShaderStages::VERTEX,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
+                    has_dynamic_offset: Self::is_model_binding(index as usize),
                     min_binding_size: None,
                 },
                 count: None,
             })
             .collect::<Vec<_>>();
-        let transform_bind_group_layout = Arc::new(device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
+        let transform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("transform"),
                 entries: transform_bind_group_layout_entries.as_slice(),
-            },
-        ));
+            });
 
-        let transform_bind_group_entries = (0..6)
-            .map(|index| wgpu::BindGroupEntry {
-                binding: index,
-                resource: transform_buffers[index as usize].as_entire_binding(),
+        let transform_bind_group = Self::create_transform_bind_group(
+            device,
+            &transform_bind_group_layout,
+            transform_buffers.as_slice(),
+        );
+
+        Self {
+            transform_buffers,
+            transform_bind_group_layout,
+            transform_bind_group,
+            material_bind_group_layout: materials.bind_group_layout.clone(),
+            deformations,
+            deformation_bindings: DeformationBindings::default(),
+            morph_targets_bind_group_layout: materials.morph_targets_bind_group_layout.clone(),
+            meshes: Vec::new(),
+            reverse_z: false,
+        }
+    }
+
+    const fn is_model_binding(index: usize) -> bool {
+        index == Self::TRANSFORM_MODEL_MATRIX_IDX || index == Self::TRANSFORM_MODEL_INV_MATRIX_IDX
+    }
+
+    fn create_transform_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        transform_buffers: &[wgpu::Buffer],
+    ) -> wgpu::BindGroup {
+        let transform_bind_group_entries = transform_buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| wgpu::BindGroupEntry {
+                binding: index as u32,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<Matrix4<f32>>() as u64),
+                }),
             })
             .collect::<Vec<_>>();
-        let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("transform"),
-            layout: &transform_bind_group_layout,
+            layout,
             entries: transform_bind_group_entries.as_slice(),
-        });
+        })
+    }
+}
 
-        let material_bind_group_layout_entries = (0..5)
-            .flat_map(|i| {
-                [
-                    wgpu::BindGroupLayoutEntry {
-                        binding: (i * 2) as u32,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: (i * 2 + 1) as u32,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ]
-            })
-            .collect::<Vec<_>>();
-        let material_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("material"),
-                entries: material_bind_group_layout_entries.as_slice(),
-            });
+impl Pass for GeometryPass {
+    fn name(&self) -> &'static str {
+        "geometry"
+    }
+
+    fn outputs(&self) -> Vec<Attachment> {
+        vec![
+            Attachment::new(Self::POSITION, Self::GBUFFER_POSITION_TEXTURE_FORMAT),
+            Attachment::new(Self::NORMAL, Self::GBUFFER_NORMAL_TEXTURE_FORMAT),
+            Attachment::new(Self::BASE_COLOR, Self::GBUFFER_BASE_COLOR_TEXTURE_FORMAT),
+            Attachment::new(Self::MATERIAL, Self::GBUFFER_MATERIAL_TEXTURE_FORMAT),
+            Attachment::new(Self::EMISSIVE, Self::GBUFFER_EMISSIVE_TEXTURE_FORMAT),
+            Attachment::new(Self::DEPTH, Self::GBUFFER_DEPTH_STENCIL_TEXTURE_FORMAT),
+        ]
+    }
 
-        let render_node = RenderNodeBuilder::default()
-            .with_name("geometry")
-            .with_color_attachment_format(Self::GBUFFER_POSITION_TEXTURE_FORMAT)
-            .with_color_attachment_format(Self::GBUFFER_NORMAL_TEXTURE_FORMAT)
-            .with_color_attachment_format(Self::GBUFFER_ALBEDO_TEXTURE_FORMAT)
-            .with_depth_stencil_format(Self::GBUFFER_DEPTH_STENCIL_TEXTURE_FORMAT)
+    fn configure<'a>(&'a self, builder: RenderNodeBuilder<'a>) -> RenderNodeBuilder<'a> {
+        builder
             .with_shader_source(include_str!("geometry.wgsl").into())
-            .with_bind_group_layout(&transform_bind_group_layout)
-            .with_bind_group_layout(&material_bind_group_layout)
+            .with_bind_group_layout(&self.transform_bind_group_layout)
+            .with_bind_group_layout(&self.material_bind_group_layout)
+            .with_bind_group_layout(&self.deformations.bind_group_layout)
+            .with_bind_group_layout(&self.morph_targets_bind_group_layout)
             .with_vertex_buffer_layout(VertexAttribute::desc())
-            .build(&device, size);
+    }
 
-        Self {
-            render_node,
-            transform_buffers,
-            transform_bind_group,
+    fn depth_compare(&self) -> wgpu::CompareFunction {
+        if self.reverse_z {
+            wgpu::CompareFunction::Greater
+        } else {
+            wgpu::CompareFunction::Less
         }
     }
 
-    pub fn pass(
-        &self,
-        queue: &wgpu::Queue,
-        encoder: &mut wgpu::CommandEncoder,
-        projection_matrix: Matrix4<f32>,
-        view_matrix: Matrix4<f32>,
-        geometries: Vec<(&Scene, &Matrix4<f32>)>,
-    ) {
-        let mut render_pass = self.render_node.begin_render_pass(encoder);
+    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &SubWorld) {
+        let (projection, view) = <(&Projection, &View)>::query().iter(world).next().unwrap();
+        self.reverse_z = projection.is_reverse_z();
+        let projection_matrix = projection.as_matrix();
+        let view_matrix = view.as_matrix();
 
         [
             (Self::TRANSFORM_PROJECTION_MATRIX_IDX, projection_matrix),
@@ -182,42 +460,78 @@ impl GeometryPass {
                 &self.transform_buffers[*index],
                 0,
                 bytemuck::cast_slice(matrix.as_slice()),
-            )
+            );
         });
 
-        render_pass.set_bind_group(0, &self.transform_bind_group, &[]);
-        geometries.iter().for_each(|(scene, transform_matrix)| {
-            let mut dfs = Dfs::new(scene, petgraph::graph::node_index(0));
-            while let Some(index) = dfs.next(scene) {
-                let node = &scene[index];
-                if let Some(mesh) = &node.mesh {
-                    let model_matrix = node.transform_matrix
-                        * dfs.stack.iter().fold(Matrix4::identity(), |acc, &nx| {
-                            acc * scene[nx].transform_matrix
-                        })
-                        * *transform_matrix;
-                    queue.write_buffer(
-                        &self.transform_buffers[Self::TRANSFORM_MODEL_MATRIX_IDX],
-                        0,
-                        bytemuck::cast_slice(model_matrix.as_slice()),
-                    );
-                    let inv_model_matrix = model_matrix.try_inverse().unwrap();
-                    queue.write_buffer(
-                        &self.transform_buffers[Self::TRANSFORM_MODEL_INV_MATRIX_IDX],
-                        0,
-                        bytemuck::cast_slice(inv_model_matrix.as_slice()),
-                    );
-                    mesh.primitives.iter().for_each(|primitive| {
-                        render_pass.set_bind_group(1, &primitive.material.material_bind_group, &[]);
-                        render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
-                        render_pass.set_index_buffer(
-                            primitive.index_buffer.slice(..),
-                            wgpu::IndexFormat::Uint32,
-                        );
-                        render_pass.draw_indexed(0..primitive.index_count, 0, 0..1);
-                    });
-                }
-            }
+        let instances = scene::meshes(world);
+        let size = instances.len() as u64 * Self::MODEL_STRIDE;
+        if size > self.transform_buffers[Self::TRANSFORM_MODEL_MATRIX_IDX].size() {
+            [
+                Self::TRANSFORM_MODEL_MATRIX_IDX,
+                Self::TRANSFORM_MODEL_INV_MATRIX_IDX,
+            ]
+            .iter()
+            .for_each(|&index| {
+                self.transform_buffers[index] = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("transform"),
+                    size,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+            });
+            self.transform_bind_group = Self::create_transform_bind_group(
+                device,
+                &self.transform_bind_group_layout,
+                self.transform_buffers.as_slice(),
+            );
+        }
+        instances.iter().enumerate().for_each(|(index, instance)| {
+            let offset = index as u64 * Self::MODEL_STRIDE;
+            queue.write_buffer(
+                &self.transform_buffers[Self::TRANSFORM_MODEL_MATRIX_IDX],
+                offset,
+                bytemuck::cast_slice(instance.model_matrix.as_slice()),
+            );
+            // Animations may scale a node to zero, normals don't depend on this matrix so any
+            // generalized inverse will do.
+            let inv_model_matrix = instance
+                .model_matrix
+                .try_inverse()
+                .unwrap_or_else(|| instance.model_matrix.pseudo_inverse(f32::EPSILON).unwrap());
+            queue.write_buffer(
+                &self.transform_buffers[Self::TRANSFORM_MODEL_INV_MATRIX_IDX],
+                offset,
+                bytemuck::cast_slice(inv_model_matrix.as_slice()),
+            );
+        });
+        self.deformation_bindings = self.deformations.bindings();
+        self.meshes = instances
+            .into_iter()
+            .map(|instance| instance.mesh)
+            .collect();
+    }
+
+    fn topologies(&self) -> Vec<wgpu::PrimitiveTopology> {
+        vec![
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::PrimitiveTopology::TriangleStrip,
+            wgpu::PrimitiveTopology::LineList,
+            wgpu::PrimitiveTopology::LineStrip,
+            wgpu::PrimitiveTopology::PointList,
+        ]
+    }
+
+    fn execute<'a>(&'a self, node: &'a RenderNode, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.meshes.iter().enumerate().for_each(|(instance, mesh)| {
+            let offset = instance as u32 * Self::MODEL_STRIDE as u32;
+            render_pass.set_bind_group(0, &self.transform_bind_group, &[offset, offset]);
+            self.deformation_bindings.bind(render_pass, 2, instance);
+            mesh.primitives.iter().for_each(|primitive| {
+                render_pass.set_pipeline(node.render_pipeline(primitive.topology).unwrap());
+                render_pass.set_bind_group(1, &primitive.material.material_bind_group, &[]);
+                render_pass.set_bind_group(3, &primitive.morph_targets.bind_group, &[]);
+                primitive.draw(render_pass);
+            });
         });
     }
 }
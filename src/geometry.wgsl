@@ -5,28 +5,92 @@
 @group(0) @binding(4) var<uniform> projection_matrix     : mat4x4<f32>;
 @group(0) @binding(5) var<uniform> inv_projection_matrix : mat4x4<f32>;
 
+// Joint matrices of the skin the vertices follow, already holding the world transform.
+@group(2) @binding(0) var<storage, read> joint_matrices : array<mat4x4<f32>>;
+
+fn skin_matrix(joints : vec4<u32>, weights : vec4<f32>) -> mat4x4<f32> {
+    return weights.x * joint_matrices[joints.x]
+         + weights.y * joint_matrices[joints.y]
+         + weights.z * joint_matrices[joints.z]
+         + weights.w * joint_matrices[joints.w];
+}
+
+// Transforms normals like the inverse transpose of the upper 3x3 of `m` up to a scale, so they stay
+// perpendicular under non-uniform scale. Unlike the inverse it exists for axes scaled to zero.
+// Mirroring transforms flip the sign.
+fn cofactor_matrix(m : mat4x4<f32>) -> mat3x3<f32> {
+    let x = m[0].xyz;
+    let y = m[1].xyz;
+    let z = m[2].xyz;
+    let cofactor = mat3x3<f32>(cross(y, z), cross(z, x), cross(x, y));
+    return cofactor * select(1.0, -1.0, dot(x, cross(y, z)) < 0.0);
+}
+
+@group(2) @binding(1) var<storage, read> morph_weights : array<f32>;
+
+struct MorphDelta {
+    position : vec4<f32>,
+    normal   : vec4<f32>,
+}
+struct MorphTargetCounts {
+    vertex_count : u32,
+    target_count : u32,
+}
+// Displacements of the primitive's morph targets, target after target with one per vertex.
+@group(3) @binding(0) var<storage, read> morph_deltas : array<MorphDelta>;
+@group(3) @binding(1) var<uniform> morph_target_counts : MorphTargetCounts;
+
+fn morph_delta(vertex_index : u32) -> MorphDelta {
+    var delta : MorphDelta;
+    for (var index = 0u; index < morph_target_counts.target_count; index++) {
+        let target_delta = morph_deltas[index * morph_target_counts.vertex_count + vertex_index];
+        delta.position += morph_weights[index] * target_delta.position;
+        delta.normal += morph_weights[index] * target_delta.normal;
+    }
+    return delta;
+}
+
 struct VertexOutput {
     @builtin(position) position : vec4<f32>,
     @location(0) normal         : vec3<f32>,
     @location(1) color_0        : vec4<f32>,
     @location(2) tex_coord_0    : vec2<f32>,
+    @location(3) world_position : vec3<f32>,
+    @location(4) tangent        : vec4<f32>,
+    @location(5) tex_coord_1    : vec2<f32>,
 }
 
 @vertex fn vertex(
+    @builtin(vertex_index) vertex_index : u32,
     @location(0) in_position    : vec3<f32>,
     @location(1) in_normal      : vec3<f32>,
     @location(2) in_color_0     : vec4<f32>,
     @location(3) in_tex_coord_0 : vec2<f32>,
+    @location(4) in_tangent     : vec4<f32>,
+    @location(5) in_joints_0    : vec4<u32>,
+    @location(6) in_weights_0   : vec4<f32>,
+    @location(7) in_tex_coord_1 : vec2<f32>,
 ) -> VertexOutput {
-    let world_position = model_matrix * vec4(in_position, 1.0);
+    var world_matrix = model_matrix;
+    var normal_matrix = cofactor_matrix(model_matrix);
+    if dot(in_weights_0, vec4(1.0)) > 0.0 {
+        world_matrix = skin_matrix(in_joints_0, in_weights_0);
+        normal_matrix = cofactor_matrix(world_matrix);
+    }
+
+    let delta = morph_delta(vertex_index);
+    let world_position = world_matrix * vec4(in_position + delta.position.xyz, 1.0);
     let view_position = view_matrix * world_position;
     let clip_position = projection_matrix * view_position;
 
     var out : VertexOutput;
     out.position = clip_position;
-    out.normal = normalize((transpose(inv_model_matrix) * vec4<f32>(in_normal, 0.0)).xyz);
+    out.normal = normalize(normal_matrix * (in_normal + delta.normal.xyz));
     out.color_0 = in_color_0;
     out.tex_coord_0 = vec2<f32>(in_tex_coord_0.x, 1.0 - in_tex_coord_0.y);
+    out.tex_coord_1 = vec2<f32>(in_tex_coord_1.x, 1.0 - in_tex_coord_1.y);
+    out.world_position = world_position.xyz;
+    out.tangent = vec4<f32>(normalize((world_matrix * vec4<f32>(in_tangent.xyz, 0.0)).xyz), in_tangent.w);
 
     return out;
 }
@@ -42,10 +106,40 @@ struct VertexOutput {
 @group(1) @binding(8) var metallic_roughness_texture : texture_2d<f32>;
 @group(1) @binding(9) var metallic_roughness_sampler : sampler;
 
+struct Material {
+    base_color_factor            : vec4<f32>,
+    emissive_factor              : vec3<f32>,
+    metallic_factor              : f32,
+    roughness_factor             : f32,
+    occlusion_strength           : f32,
+    normal_scale                 : f32,
+    emissive_transform           : mat3x3<f32>,
+    normal_transform             : mat3x3<f32>,
+    occlusion_transform          : mat3x3<f32>,
+    base_color_transform         : mat3x3<f32>,
+    metallic_roughness_transform : mat3x3<f32>,
+    emissive_tex_coord           : u32,
+    normal_tex_coord             : u32,
+    occlusion_tex_coord          : u32,
+    base_color_tex_coord         : u32,
+    metallic_roughness_tex_coord : u32,
+}
+@group(1) @binding(10) var<uniform> material : Material;
+
+// Texture coordinates of a material slot from the set it samples. The vertex stage flips them
+// vertically, transforms apply to the glTF ones.
+fn slot_tex_coord(tex_coord_set : u32, transform : mat3x3<f32>, tex_coord_0 : vec2<f32>, tex_coord_1 : vec2<f32>) -> vec2<f32> {
+    let tex_coord = select(tex_coord_0, tex_coord_1, tex_coord_set == 1u);
+    let transformed = (transform * vec3<f32>(tex_coord.x, 1.0 - tex_coord.y, 1.0)).xy;
+    return vec2<f32>(transformed.x, 1.0 - transformed.y);
+}
+
 struct FragmentOutput {
-    @location(0) position : vec4<f32>,
-    @location(1) normal   : vec4<f32>,
-    @location(2) albedo   : vec4<f32>,
+    @location(0) position   : vec4<f32>,
+    @location(1) normal     : vec4<f32>,
+    @location(2) base_color : vec4<f32>,
+    @location(3) material   : vec4<f32>,
+    @location(4) emissive   : vec4<f32>,
 }
 
 @fragment fn fragment(
@@ -53,17 +147,33 @@ struct FragmentOutput {
     @location(0) in_normal         : vec3<f32>,
     @location(1) in_color_0        : vec4<f32>,
     @location(2) in_tex_coord_0    : vec2<f32>,
+    @location(3) in_world_position : vec3<f32>,
+    @location(4) in_tangent        : vec4<f32>,
+    @location(5) in_tex_coord_1    : vec2<f32>,
 ) -> FragmentOutput {
     var out : FragmentOutput;
-    out.position = in_position;
-    out.normal = vec4(in_normal, 1.0);
-
-    let emissive_color = textureSample(emissive_texture, emissive_sampler, in_tex_coord_0);
-    let normal_color = textureSample(normal_texture, normal_sampler, in_tex_coord_0);
-    let occlusion_color = textureSample(occlusion_texture, occlusion_sampler, in_tex_coord_0);
-    let base_color_color = textureSample(base_color_texture, base_color_sampler, in_tex_coord_0);
-    let metallic_roughness_color = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in_tex_coord_0);
-    out.albedo = in_color_0 * emissive_color * normal_color * occlusion_color * base_color_color * metallic_roughness_color;
-    
+    out.position = vec4(in_world_position, 1.0);
+
+    let normal_color = textureSample(normal_texture, normal_sampler, slot_tex_coord(material.normal_tex_coord, material.normal_transform, in_tex_coord_0, in_tex_coord_1));
+    let N = normalize(in_normal);
+    let T = normalize(in_tangent.xyz - N * dot(N, in_tangent.xyz));
+    let B = cross(N, T) * in_tangent.w;
+    let tangent_normal = (normal_color.rgb * 2.0 - 1.0) * vec3<f32>(material.normal_scale, material.normal_scale, 1.0);
+    out.normal = vec4(normalize(mat3x3<f32>(T, B, N) * tangent_normal), 1.0);
+
+    let emissive_color = textureSample(emissive_texture, emissive_sampler, slot_tex_coord(material.emissive_tex_coord, material.emissive_transform, in_tex_coord_0, in_tex_coord_1));
+    let occlusion_color = textureSample(occlusion_texture, occlusion_sampler, slot_tex_coord(material.occlusion_tex_coord, material.occlusion_transform, in_tex_coord_0, in_tex_coord_1));
+    let base_color_color = textureSample(base_color_texture, base_color_sampler, slot_tex_coord(material.base_color_tex_coord, material.base_color_transform, in_tex_coord_0, in_tex_coord_1));
+    let metallic_roughness_color = textureSample(metallic_roughness_texture, metallic_roughness_sampler, slot_tex_coord(material.metallic_roughness_tex_coord, material.metallic_roughness_transform, in_tex_coord_0, in_tex_coord_1));
+
+    out.base_color = in_color_0 * material.base_color_factor * base_color_color;
+
+    let occlusion = 1.0 + material.occlusion_strength * (occlusion_color.r - 1.0);
+    let roughness = metallic_roughness_color.g * material.roughness_factor;
+    let metallic = metallic_roughness_color.b * material.metallic_factor;
+    out.material = vec4(occlusion, roughness, metallic, 1.0);
+
+    out.emissive = vec4(material.emissive_factor * emissive_color.rgb, 1.0);
+
     return out;
 }
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use legion::world::SubWorld;
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+
+use crate::renderer::{RenderNode, RenderNodeBuilder, RenderTarget};
+
+/// A named texture written by a pass and sampled by the passes that list it as an input.
+pub struct Attachment {
+    pub name: &'static str,
+    pub format: wgpu::TextureFormat,
+    /// Overrides the frame size, attachments with several array layers are sampled as 2D arrays
+    /// unless `view_dimension` says otherwise, and rendered one layer at a time.
+    pub size: Option<wgpu::Extent3d>,
+    pub view_dimension: Option<wgpu::TextureViewDimension>,
+    /// How the passes reading the attachment sample it, depth attachments sampled with a
+    /// comparison sampler need `Depth`.
+    pub sample_type: wgpu::TextureSampleType,
+}
+
+impl Attachment {
+    pub const fn new(name: &'static str, format: wgpu::TextureFormat) -> Self {
+        Self {
+            name,
+            format,
+            size: None,
+            view_dimension: None,
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+        }
+    }
+
+    pub const fn with_size(mut self, size: wgpu::Extent3d) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub const fn with_view_dimension(mut self, view_dimension: wgpu::TextureViewDimension) -> Self {
+        self.view_dimension = Some(view_dimension);
+        self
+    }
+
+    pub const fn with_sample_type(mut self, sample_type: wgpu::TextureSampleType) -> Self {
+        self.sample_type = sample_type;
+        self
+    }
+}
+
+pub trait Pass: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Attachments sampled by the pass, bound in order at bind group 0.
+    fn inputs(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn outputs(&self) -> Vec<Attachment>;
+
+    /// Adds the shader, bind group layouts and vertex layouts of the pass to a builder already
+    /// holding its input and output attachments.
+    fn configure<'a>(&'a self, builder: RenderNodeBuilder<'a>) -> RenderNodeBuilder<'a>;
+
+    /// Depth test of the pass, the depth attachment is cleared to 0 instead of 1 for the
+    /// greater comparisons of reversed depth.
+    fn depth_compare(&self) -> wgpu::CompareFunction {
+        wgpu::CompareFunction::Less
+    }
+
+    /// Winding of the front faces, back faces are culled.
+    fn front_face(&self) -> wgpu::FrontFace {
+        wgpu::FrontFace::Ccw
+    }
+
+    /// Primitive topologies drawn by the pass, a pipeline is built for each of them and the first
+    /// one is bound when its render passes begin.
+    fn topologies(&self) -> Vec<wgpu::PrimitiveTopology> {
+        vec![wgpu::PrimitiveTopology::TriangleList]
+    }
+
+    /// Uploads the per-frame data of the pass before any render pass is recorded.
+    fn prepare(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue, _world: &SubWorld) {}
+
+    /// Number of render passes recorded this frame, each one targeting the matching layer of
+    /// the layered outputs.
+    fn layers(&self) -> u32 {
+        1
+    }
+
+    /// Records the draws of the pass, `node` holds the pipelines to switch between topologies.
+    fn execute<'a>(&'a self, node: &'a RenderNode, render_pass: &mut wgpu::RenderPass<'a>);
+
+    fn execute_layer<'a>(
+        &'a self,
+        _layer: u32,
+        node: &'a RenderNode,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        self.execute(node, render_pass);
+    }
+}
+
+struct GraphAttachment {
+    view: wgpu::TextureView,
+    view_dimension: wgpu::TextureViewDimension,
+    sample_type: wgpu::TextureSampleType,
+    layer_views: Vec<wgpu::TextureView>,
+}
+
+pub struct RenderGraph {
+    graph: StableDiGraph<Box<dyn Pass>, &'static str>,
+    order: Vec<NodeIndex>,
+    nodes: HashMap<NodeIndex, RenderNode>,
+    attachments: HashMap<&'static str, GraphAttachment>,
+    size: wgpu::Extent3d,
+    dirty: bool,
+}
+
+impl RenderGraph {
+    /// Attachment written to the surface or offscreen texture the frame is presented to.
+    pub const BACKBUFFER: &'static str = "backbuffer";
+
+    pub fn new(size: wgpu::Extent3d) -> Self {
+        Self {
+            graph: StableDiGraph::default(),
+            order: Vec::new(),
+            nodes: HashMap::new(),
+            attachments: HashMap::new(),
+            size,
+            dirty: false,
+        }
+    }
+
+    pub fn add_pass<P: Pass + 'static>(&mut self, pass: P) -> NodeIndex {
+        self.dirty = true;
+        self.graph.add_node(Box::new(pass))
+    }
+
+    pub fn remove_pass(&mut self, index: NodeIndex) -> Option<Box<dyn Pass>> {
+        self.dirty = true;
+        self.graph.remove_node(index)
+    }
+
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &SubWorld) {
+        if self.dirty {
+            self.compile(device);
+        }
+        self.order
+            .iter()
+            .for_each(|&index| self.graph[index].prepare(device, queue, world));
+
+        // Passes may switch their depth test when the camera changes, rebuilding the pipelines.
+        let stale = self
+            .order
+            .iter()
+            .any(|index| self.nodes[index].depth_compare != self.graph[*index].depth_compare());
+        if stale {
+            self.compile(device);
+        }
+    }
+
+    pub fn execute(&self, encoder: &mut wgpu::CommandEncoder, backbuffer: &wgpu::TextureView) {
+        self.order.iter().for_each(|&index| {
+            let pass = &self.graph[index];
+            let outputs = pass.outputs();
+            (0..pass.layers()).for_each(|layer| {
+                let attachment_view = |attachment: &Attachment| {
+                    if attachment.name == Self::BACKBUFFER {
+                        return backbuffer;
+                    }
+                    let graph_attachment = &self.attachments[attachment.name];
+                    graph_attachment
+                        .layer_views
+                        .get(layer as usize)
+                        .unwrap_or(&graph_attachment.view)
+                };
+                let color_views = outputs
+                    .iter()
+                    .filter(|attachment| !attachment.format.is_depth_stencil_format())
+                    .map(attachment_view)
+                    .collect::<Vec<_>>();
+                let depth_stencil_view = outputs
+                    .iter()
+                    .find(|attachment| attachment.format.is_depth_stencil_format())
+                    .map(attachment_view);
+
+                let node = &self.nodes[&index];
+                let mut render_pass =
+                    node.begin_render_pass(encoder, color_views.as_slice(), depth_stencil_view);
+                pass.execute_layer(layer, node, &mut render_pass);
+            });
+        });
+    }
+
+    /// Links every input to the pass writing it, sorts the passes topologically and allocates
+    /// their attachments, input bind groups and pipelines.
+    fn compile(&mut self, device: &wgpu::Device) {
+        let producers = self
+            .graph
+            .node_indices()
+            .flat_map(|index| {
+                self.graph[index]
+                    .outputs()
+                    .into_iter()
+                    .map(move |attachment| (attachment.name, (index, attachment)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.graph.clear_edges();
+        let edges = self
+            .graph
+            .node_indices()
+            .flat_map(|index| {
+                self.graph[index]
+                    .inputs()
+                    .into_iter()
+                    .map(|name| {
+                        let (producer, _) = producers.get(name).unwrap_or_else(|| {
+                            panic!(
+                                "no pass writes attachment {name} read by {}",
+                                self.graph[index].name()
+                            )
+                        });
+                        (*producer, index, name)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        edges.into_iter().for_each(|(producer, consumer, name)| {
+            self.graph.add_edge(producer, consumer, name);
+        });
+        self.order = petgraph::algo::toposort(&self.graph, None).unwrap_or_else(|cycle| {
+            panic!(
+                "render graph has a cycle through {}",
+                self.graph[cycle.node_id()].name()
+            )
+        });
+
+        self.attachments = producers
+            .iter()
+            .filter(|(&name, _)| name != Self::BACKBUFFER)
+            .map(|(&name, (_, attachment))| (name, self.allocate(device, attachment)))
+            .collect();
+
+        self.nodes = self
+            .order
+            .iter()
+            .map(|&index| {
+                let pass = &self.graph[index];
+                let inputs = pass.inputs();
+                let input = (!inputs.is_empty()).then(|| {
+                    let views = inputs
+                        .iter()
+                        .map(|&name| {
+                            let attachment = &self.attachments[name];
+                            (
+                                &attachment.view,
+                                attachment.view_dimension,
+                                attachment.sample_type,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    RenderTarget::new(device, Some(pass.name()), views.as_slice())
+                });
+
+                let builder = pass.outputs().into_iter().fold(
+                    RenderNodeBuilder::default().with_name(pass.name()),
+                    |builder, attachment| {
+                        if attachment.format.is_depth_stencil_format() {
+                            builder.with_depth_stencil_format(attachment.format)
+                        } else {
+                            builder.with_color_attachment_format(attachment.format)
+                        }
+                    },
+                );
+                let builder = match &input {
+                    Some(input) => builder.with_input(input),
+                    None => builder,
+                };
+                let depth_compare = pass.depth_compare();
+                let builder = builder
+                    .with_depth_compare(depth_compare)
+                    .with_front_face(pass.front_face());
+                let render_pipelines = pass
+                    .topologies()
+                    .into_iter()
+                    .map(|topology| {
+                        let builder = builder.clone().with_topology(topology);
+                        (topology, pass.configure(builder).build(device))
+                    })
+                    .collect();
+
+                (
+                    index,
+                    RenderNode {
+                        input,
+                        render_pipelines,
+                        depth_compare,
+                    },
+                )
+            })
+            .collect();
+
+        self.dirty = false;
+    }
+
+    fn allocate(&self, device: &wgpu::Device, attachment: &Attachment) -> GraphAttachment {
+        let size = attachment.size.unwrap_or(self.size);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(attachment.name),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: attachment.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        if size.depth_or_array_layers == 1 {
+            return GraphAttachment {
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: attachment.sample_type,
+                layer_views: Vec::new(),
+            };
+        }
+        let layer_views = (0..size.depth_or_array_layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some(attachment.name),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..wgpu::TextureViewDescriptor::default()
+                })
+            })
+            .collect();
+        let view_dimension = attachment
+            .view_dimension
+            .unwrap_or(wgpu::TextureViewDimension::D2Array);
+        GraphAttachment {
+            view: texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some(attachment.name),
+                dimension: Some(view_dimension),
+                ..wgpu::TextureViewDescriptor::default()
+            }),
+            view_dimension,
+            sample_type: attachment.sample_type,
+            layer_views,
+        }
+    }
+}
@@ -0,0 +1,655 @@
+use thiserror::Error;
+
+mod basisu;
+mod uastc;
+mod zstd;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_SIZE: usize = 24;
+/// Largest width or height of a 2D texture on any device.
+const MAX_DIMENSION: u32 = 16384;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+const COLOR_MODEL_ETC1S: u8 = 163;
+const COLOR_MODEL_UASTC: u8 = 166;
+const TRANSFER_SRGB: u8 = 2;
+const CHANNEL_ALPHA: u8 = 15;
+
+#[derive(Debug, Error)]
+pub enum Ktx2Error {
+    #[error("not a KTX2 container")]
+    Identifier,
+    #[error("truncated KTX2 container")]
+    Truncated,
+    #[error("KTX2 supercompression scheme {0} is not supported")]
+    Supercompression(u32),
+    #[error("malformed zstd supercompressed level: {0}")]
+    Zstd(&'static str),
+    #[error("malformed ETC1S payload: {0}")]
+    Etc1s(&'static str),
+    #[error("malformed UASTC block: {0}")]
+    Uastc(&'static str),
+    #[error("KTX2 Vulkan format {0} has no matching texture format")]
+    Format(u32),
+    #[error("KTX2 arrays, cube maps, 3D and oversized textures are not supported")]
+    Dimension,
+    #[error("KTX2 container has {0} levels, more than its size allows")]
+    LevelCount(u32),
+    #[error("KTX2 level {level} holds {len} bytes instead of {expected}")]
+    LevelSize {
+        level: usize,
+        len: usize,
+        expected: usize,
+    },
+}
+
+/// A 2D texture read from a KTX2 container, with the mip levels it was authored with.
+pub struct Ktx2 {
+    pub format: wgpu::TextureFormat,
+    pub size: wgpu::Extent3d,
+    /// Texels of every mip level, the base level first.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2 {
+    /// Reads a container and decompresses its zstd supercompressed levels. Basis Universal
+    /// payloads are transcoded to the first format `features` allow: ETC2, BC1/BC3 or RGBA8
+    /// for ETC1S, and ASTC 4x4 or RGBA8 for UASTC.
+    pub fn parse(bytes: &[u8], features: wgpu::Features) -> Result<Self, Ktx2Error> {
+        if !bytes.starts_with(&IDENTIFIER) {
+            return Err(Ktx2Error::Identifier);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(Ktx2Error::Truncated);
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let u64_at =
+            |offset: usize| u64::from(u32_at(offset)) | u64::from(u32_at(offset + 4)) << 32;
+        let range = |offset: u64, length: u64| {
+            let offset = usize::try_from(offset).map_err(|_| Ktx2Error::Truncated)?;
+            let length = usize::try_from(length).map_err(|_| Ktx2Error::Truncated)?;
+            bytes
+                .get(offset..offset.saturating_add(length))
+                .ok_or(Ktx2Error::Truncated)
+        };
+
+        let vk_format = u32_at(12);
+        let [width, height, depth, layer_count, face_count, level_count, supercompression] =
+            [20, 24, 28, 32, 36, 40, 44].map(u32_at);
+        if !(1..=MAX_DIMENSION).contains(&width)
+            || !(1..=MAX_DIMENSION).contains(&height)
+            || depth != 0
+            || layer_count != 0
+            || face_count != 1
+        {
+            return Err(Ktx2Error::Dimension);
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        if level_count > size.max_mips(wgpu::TextureDimension::D2) {
+            return Err(Ktx2Error::LevelCount(level_count));
+        }
+
+        // A level count of 0 asks for mipmaps to be generated, the container holds one level.
+        let level_count = level_count.max(1) as usize;
+        if bytes.len() < HEADER_SIZE + level_count * LEVEL_SIZE {
+            return Err(Ktx2Error::Truncated);
+        }
+        let levels = (0..level_count)
+            .map(|level| {
+                let index = HEADER_SIZE + level * LEVEL_SIZE;
+                let data = range(u64_at(index), u64_at(index + 8))?;
+                match supercompression {
+                    0 | SUPERCOMPRESSION_BASIS_LZ => Ok(data.to_vec()),
+                    SUPERCOMPRESSION_ZSTD => {
+                        let level = zstd::decompress(data)?;
+                        if level.len() as u64 != u64_at(index + 16) {
+                            return Err(Ktx2Error::Zstd("level size mismatch"));
+                        }
+                        Ok(level)
+                    }
+                    _ => Err(Ktx2Error::Supercompression(supercompression)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if vk_format == 0 {
+            // Basis Universal payloads describe themselves in the data format descriptor.
+            let dfd = range(u64::from(u32_at(48)), u64::from(u32_at(52)))?;
+            if dfd.len() < 28 {
+                return Err(Ktx2Error::Truncated);
+            }
+            let block_size = usize::from(u16::from_le_bytes([dfd[10], dfd[11]]));
+            let samples = dfd.get(28..4 + block_size).ok_or(Ktx2Error::Truncated)?;
+            let alpha = samples
+                .chunks_exact(16)
+                .any(|sample| sample[3] & 0xF == CHANNEL_ALPHA);
+            let (format, levels) = match (dfd[12], supercompression) {
+                (COLOR_MODEL_ETC1S, SUPERCOMPRESSION_BASIS_LZ) => {
+                    let global_data = range(u64_at(64), u64_at(72))?;
+                    let format = basisu::target_format(features, alpha, width, height);
+                    let levels =
+                        basisu::transcode(global_data, &levels, width, height, alpha, format)?;
+                    (format, levels)
+                }
+                (COLOR_MODEL_UASTC, 0 | SUPERCOMPRESSION_ZSTD) => {
+                    let format = uastc::target_format(features, width, height);
+                    (format, uastc::transcode(&levels, width, height, format)?)
+                }
+                (COLOR_MODEL_UASTC, _) => {
+                    return Err(Ktx2Error::Supercompression(supercompression))
+                }
+                _ => return Err(Ktx2Error::Format(vk_format)),
+            };
+            let format = if dfd[14] == TRANSFER_SRGB {
+                format.add_srgb_suffix()
+            } else {
+                format
+            };
+            return Ok(Self {
+                format,
+                size,
+                levels,
+            });
+        }
+
+        if supercompression == SUPERCOMPRESSION_BASIS_LZ {
+            return Err(Ktx2Error::Supercompression(supercompression));
+        }
+        let format = texture_format(vk_format).ok_or(Ktx2Error::Format(vk_format))?;
+        let (block_width, block_height) = format.block_dimensions();
+        if width % block_width != 0 || height % block_height != 0 {
+            return Err(Ktx2Error::Dimension);
+        }
+        // Uploads read whole levels, a short one would fail in wgpu instead of falling back.
+        let block_size = format.block_size(None).unwrap() as usize;
+        for (level, data) in levels.iter().enumerate() {
+            let mip_size = size
+                .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+            let expected = (mip_size.width / block_width) as usize
+                * (mip_size.height / block_height) as usize
+                * block_size;
+            if data.len() != expected {
+                return Err(Ktx2Error::LevelSize {
+                    level,
+                    len: data.len(),
+                    expected,
+                });
+            }
+        }
+
+        Ok(Self {
+            format,
+            size,
+            levels,
+        })
+    }
+}
+
+/// Texture format of a `VkFormat`, limited to the formats holding colors in all four channels.
+fn texture_format(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    let format = match vk_format {
+        37 => wgpu::TextureFormat::Rgba8Unorm,
+        43 => wgpu::TextureFormat::Rgba8UnormSrgb,
+        97 => wgpu::TextureFormat::Rgba16Float,
+        131 | 133 => wgpu::TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+        135 => wgpu::TextureFormat::Bc2RgbaUnorm,
+        136 => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+        137 => wgpu::TextureFormat::Bc3RgbaUnorm,
+        138 => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+        145 => wgpu::TextureFormat::Bc7RgbaUnorm,
+        146 => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        147 => wgpu::TextureFormat::Etc2Rgb8Unorm,
+        148 => wgpu::TextureFormat::Etc2Rgb8UnormSrgb,
+        149 => wgpu::TextureFormat::Etc2Rgb8A1Unorm,
+        150 => wgpu::TextureFormat::Etc2Rgb8A1UnormSrgb,
+        151 => wgpu::TextureFormat::Etc2Rgba8Unorm,
+        152 => wgpu::TextureFormat::Etc2Rgba8UnormSrgb,
+        157 => wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        },
+        158 => wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fields of a KTX2 container written by `Container::bytes`.
+    struct Container {
+        vk_format: u32,
+        width: u32,
+        height: u32,
+        layer_count: u32,
+        supercompression: u32,
+        dfd: Vec<u8>,
+        global_data: Vec<u8>,
+        /// Data of each level, the base level first, and its size once decompressed.
+        levels: Vec<(Vec<u8>, usize)>,
+    }
+
+    impl Container {
+        fn new(vk_format: u32, width: u32, height: u32, levels: &[&[u8]]) -> Self {
+            Self {
+                vk_format,
+                width,
+                height,
+                layer_count: 0,
+                supercompression: 0,
+                dfd: Vec::new(),
+                global_data: Vec::new(),
+                levels: levels
+                    .iter()
+                    .map(|level| (level.to_vec(), level.len()))
+                    .collect(),
+            }
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            let index_end = HEADER_SIZE + self.levels.len() * LEVEL_SIZE;
+            let dfd_offset = index_end;
+            let global_data_offset = dfd_offset + self.dfd.len();
+            let mut bytes = IDENTIFIER.to_vec();
+            for field in [
+                self.vk_format,
+                1,
+                self.width,
+                self.height,
+                0,
+                self.layer_count,
+                1,
+                self.levels.len() as u32,
+                self.supercompression,
+                dfd_offset as u32,
+                self.dfd.len() as u32,
+                0,
+                0,
+            ] {
+                bytes.extend(field.to_le_bytes());
+            }
+            bytes.extend((global_data_offset as u64).to_le_bytes());
+            bytes.extend((self.global_data.len() as u64).to_le_bytes());
+
+            // Levels are stored from the smallest, the index lists them from the base level.
+            let mut offset = global_data_offset + self.global_data.len();
+            let mut offsets = vec![0; self.levels.len()];
+            for (level, (data, _)) in self.levels.iter().enumerate().rev() {
+                offsets[level] = offset;
+                offset += data.len();
+            }
+            for ((data, uncompressed_len), offset) in self.levels.iter().zip(offsets) {
+                for field in [offset, data.len(), *uncompressed_len] {
+                    bytes.extend((field as u64).to_le_bytes());
+                }
+            }
+            bytes.extend(&self.dfd);
+            bytes.extend(&self.global_data);
+            for (data, _) in self.levels.iter().rev() {
+                bytes.extend(data);
+            }
+            bytes
+        }
+    }
+
+    /// Basic data format descriptor with one sample per channel id.
+    fn dfd(color_model: u8, transfer: u8, channels: &[u8]) -> Vec<u8> {
+        let block_size = 24 + 16 * channels.len();
+        let mut dfd = ((4 + block_size) as u32).to_le_bytes().to_vec();
+        dfd.extend([0; 4]);
+        dfd.extend(2u16.to_le_bytes());
+        dfd.extend((block_size as u16).to_le_bytes());
+        dfd.extend([color_model, 1, transfer, 0, 3, 3, 0, 0]);
+        dfd.extend([0; 8]);
+        for &channel in channels {
+            dfd.extend([0, 0, 63, channel]);
+            dfd.extend([0; 12]);
+        }
+        dfd
+    }
+
+    fn parse(container: &Container) -> Result<Ktx2, Ktx2Error> {
+        Ktx2::parse(&container.bytes(), wgpu::Features::empty())
+    }
+
+    #[test]
+    fn reads_the_header_and_every_level() {
+        let base = [1; 4 * 4 * 4];
+        let mip = [2; 2 * 2 * 4];
+        let last = [3; 4];
+        let ktx2 = parse(&Container::new(43, 4, 4, &[&base, &mip, &last])).unwrap();
+        assert_eq!(ktx2.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(
+            ktx2.size,
+            wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            }
+        );
+        assert_eq!(ktx2.levels, [base.to_vec(), mip.to_vec(), last.to_vec()]);
+    }
+
+    #[test]
+    fn rejects_other_containers_and_layouts() {
+        let mut bytes = Container::new(37, 1, 1, &[&[0; 4]]).bytes();
+        bytes[5] = b'1';
+        assert!(matches!(
+            Ktx2::parse(&bytes, wgpu::Features::empty()),
+            Err(Ktx2Error::Identifier)
+        ));
+
+        let mut array = Container::new(37, 1, 1, &[&[0; 4]]);
+        array.layer_count = 2;
+        assert!(matches!(parse(&array), Err(Ktx2Error::Dimension)));
+        // BC1 blocks don't divide a 6 texels wide level.
+        let bc1 = Container::new(131, 6, 4, &[&[0; 16]]);
+        assert!(matches!(parse(&bc1), Err(Ktx2Error::Dimension)));
+        assert!(matches!(
+            parse(&Container::new(64, 1, 1, &[&[0; 4]])),
+            Err(Ktx2Error::Format(64))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let bytes = Container::new(37, 2, 1, &[&[0; 8], &[0; 4]]).bytes();
+        // The header, the level index and the last byte of the smallest level.
+        for len in [
+            IDENTIFIER.len(),
+            HEADER_SIZE - 1,
+            HEADER_SIZE + LEVEL_SIZE,
+            bytes.len() - 1,
+        ] {
+            assert!(
+                matches!(
+                    Ktx2::parse(&bytes[..len], wgpu::Features::empty()),
+                    Err(Ktx2Error::Truncated)
+                ),
+                "{len} bytes"
+            );
+        }
+
+        // A level past the end of the container.
+        let mut bytes = bytes;
+        bytes[HEADER_SIZE] = 0xFF;
+        assert!(matches!(
+            Ktx2::parse(&bytes, wgpu::Features::empty()),
+            Err(Ktx2Error::Truncated)
+        ));
+    }
+
+    #[test]
+    fn rejects_levels_that_dont_match_the_size() {
+        // The 1x1 level of a 2x2 texture is missing a byte.
+        let short = Container::new(37, 2, 2, &[&[0; 16], &[0; 3]]);
+        assert!(matches!(
+            parse(&short),
+            Err(Ktx2Error::LevelSize {
+                level: 1,
+                len: 3,
+                expected: 4
+            })
+        ));
+        // A BC1 level of 8x4 texels holds two blocks.
+        let bc1 = Container::new(131, 8, 4, &[&[0; 8]]);
+        assert!(matches!(
+            parse(&bc1),
+            Err(Ktx2Error::LevelSize { expected: 16, .. })
+        ));
+
+        let empty = Container::new(37, 0, 1, &[&[]]);
+        assert!(matches!(parse(&empty), Err(Ktx2Error::Dimension)));
+        // A 2x1 texture has two levels at most.
+        let levels = Container::new(37, 2, 1, &[&[0; 8], &[0; 4], &[0; 4]]);
+        assert!(matches!(parse(&levels), Err(Ktx2Error::LevelCount(3))));
+    }
+
+    #[test]
+    fn decompresses_zstd_supercompressed_levels() {
+        // A zstd frame of 64 bytes holding a single RLE block.
+        let frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x40, 0x03, 0x02, 0x00, 0x80];
+        let mut container = Container::new(37, 4, 4, &[]);
+        container.supercompression = SUPERCOMPRESSION_ZSTD;
+        container.levels = vec![(frame.clone(), 64)];
+        assert_eq!(parse(&container).unwrap().levels, [[0x80; 64].to_vec()]);
+
+        // The level index gives the size of the decompressed level.
+        container.levels = vec![(frame.clone(), 65)];
+        assert!(matches!(parse(&container), Err(Ktx2Error::Zstd(_))));
+        // A decompressed level too short for an 8x4 texture.
+        container.width = 8;
+        container.levels = vec![(frame, 64)];
+        assert!(matches!(
+            parse(&container),
+            Err(Ktx2Error::LevelSize { expected: 128, .. })
+        ));
+
+        container.supercompression = 3;
+        assert!(matches!(
+            parse(&container),
+            Err(Ktx2Error::Supercompression(3))
+        ));
+    }
+
+    /// Writes bits least significant first, the way BasisLZ streams are read.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, len: usize) -> &mut Self {
+            for bit in 0..len {
+                if self.len.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= ((value >> bit & 1) as u8) << (self.len % 8);
+                self.len += 1;
+            }
+            self
+        }
+
+        /// Code of `symbol` in a table giving every symbol `len` bits, which is the symbol
+        /// itself written from its most significant bit.
+        fn code(&mut self, symbol: u32, len: usize) -> &mut Self {
+            for bit in (0..len).rev() {
+                self.bits(symbol >> bit & 1, 1);
+            }
+            self
+        }
+
+        /// Huffman table giving every one of `symbol_count` symbols `len` bits.
+        fn table(&mut self, symbol_count: u32, len: u32) -> &mut Self {
+            // All 21 code length codes are 5 bits long.
+            self.bits(symbol_count, 14).bits(21, 5);
+            for _ in 0..21 {
+                self.bits(5, 3);
+            }
+            for _ in 0..symbol_count {
+                self.code(len, 5);
+            }
+            self
+        }
+    }
+
+    /// A 16x8 ETC1S texture of two endpoints and two selectors, with its alpha slice reusing
+    /// the color one when `alpha` is set. The blocks are, row by row:
+    ///
+    /// ```text
+    /// (E1, S0) (E1, S1) (E0, S1) (E0, S0)
+    /// (E1, S0) (E0, S0) (E1, S1) (E0, S1)
+    /// ```
+    fn etc1s(alpha: bool) -> Container {
+        // E0 is (0, 5, 16) with table 7, E1 is (20, 10, 31) with table 2. Intensities take 3
+        // bits and color deltas 5, 6 or 7 bits when the previous value is below 10, below 22
+        // or above.
+        let mut endpoints = BitWriter::default();
+        endpoints
+            .table(32, 5)
+            .table(32, 6)
+            .table(32, 7)
+            .table(8, 3)
+            .bits(0, 1);
+        endpoints.code(7, 3).code(16, 6).code(21, 6).code(0, 6);
+        endpoints.code(3, 3).code(20, 5).code(5, 5).code(15, 6);
+
+        // S0 has the modifiers of every row going down, S1 those of every column.
+        let mut selectors = BitWriter::default();
+        selectors.bits(0, 3).table(256, 8).bits(0x1B1B_1B1B, 32);
+        for row in [0x1B, 0x4E, 0xB1, 0xE4] {
+            selectors.code(row, 8);
+        }
+
+        // Selector symbols are the 2 selectors, 4 history entries and the history run.
+        let mut tables = BitWriter::default();
+        tables
+            .table(257, 9)
+            .table(2, 1)
+            .table(7, 3)
+            .table(64, 6)
+            .bits(4, 13);
+
+        let mut slice = BitWriter::default();
+        // Left blocks: delta, left, then upper and delta. Right ones: delta, left, then
+        // upper-left and upper.
+        slice.code(0b11_01_00_11, 9).code(1, 1).code(0, 3);
+        slice.code(1, 3);
+        slice.code(0b01_10_00_11, 9).code(1, 1).code(5, 3);
+        // History entry 0 for this block and the first two of the next row.
+        slice.code(6, 3).code(0, 6);
+        slice.code(1, 1);
+        slice.code(1, 3).code(3, 3);
+
+        let mut global_data = vec![2, 0, 2, 0];
+        for len in [
+            endpoints.bytes.len(),
+            selectors.bytes.len(),
+            tables.bytes.len(),
+            0,
+        ] {
+            global_data.extend((len as u32).to_le_bytes());
+        }
+        let alpha_len = if alpha { slice.bytes.len() } else { 0 };
+        for field in [0, 0, slice.bytes.len(), 0, alpha_len] {
+            global_data.extend((field as u32).to_le_bytes());
+        }
+        global_data.extend(endpoints.bytes);
+        global_data.extend(selectors.bytes);
+        global_data.extend(tables.bytes);
+
+        let mut container = Container::new(0, 16, 8, &[&slice.bytes]);
+        container.supercompression = SUPERCOMPRESSION_BASIS_LZ;
+        let channels: &[u8] = if alpha { &[0, CHANNEL_ALPHA] } else { &[0] };
+        container.dfd = dfd(COLOR_MODEL_ETC1S, TRANSFER_SRGB, channels);
+        container.global_data = global_data;
+        container
+    }
+
+    fn texel(ktx2: &Ktx2, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * ktx2.size.width as usize + x) * 4;
+        ktx2.levels[0][offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn transcodes_etc1s_to_rgba8() {
+        let ktx2 = parse(&etc1s(false)).unwrap();
+        assert_eq!(ktx2.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(ktx2.levels[0].len(), 16 * 8 * 4);
+        // E1 from its darkest to its brightest modifier.
+        assert_eq!(texel(&ktx2, 3, 0), [136, 53, 226, 255]);
+        assert_eq!(texel(&ktx2, 4, 1), [156, 73, 246, 255]);
+        assert_eq!(texel(&ktx2, 1, 4), [174, 91, 255, 255]);
+        assert_eq!(texel(&ktx2, 10, 7), [194, 111, 255, 255]);
+        // E0 in the blocks predicted from the upper-left and upper ones.
+        assert_eq!(texel(&ktx2, 9, 1), [0, 0, 85, 255]);
+        assert_eq!(texel(&ktx2, 5, 4), [47, 88, 179, 255]);
+        assert_eq!(texel(&ktx2, 15, 7), [183, 224, 255, 255]);
+
+        let ktx2 = parse(&etc1s(true)).unwrap();
+        assert_eq!(texel(&ktx2, 3, 0), [136, 53, 226, 53]);
+    }
+
+    #[test]
+    fn transcodes_etc1s_to_the_supported_block_formats() {
+        let etc2 = Ktx2::parse(
+            &etc1s(false).bytes(),
+            wgpu::Features::TEXTURE_COMPRESSION_ETC2 | wgpu::Features::TEXTURE_COMPRESSION_BC,
+        )
+        .unwrap();
+        assert_eq!(etc2.format, wgpu::TextureFormat::Etc2Rgb8UnormSrgb);
+        // E1 without delta, both halves with table 2, then the high and low index bits.
+        assert_eq!(
+            etc2.levels[0][..8],
+            [160, 80, 248, 2 << 5 | 2 << 2 | 2, 0xFF, 0x00, 0xF0, 0x0F]
+        );
+
+        let bc1 = Ktx2::parse(
+            &etc1s(false).bytes(),
+            wgpu::Features::TEXTURE_COMPRESSION_BC,
+        );
+        let bc1 = bc1.unwrap();
+        assert_eq!(bc1.format, wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!(bc1.levels[0].len(), 8 * 8);
+
+        // ETC2 carries the alpha in an EAC block before the colors, BC3 in a BC4 block.
+        let features = wgpu::Features::TEXTURE_COMPRESSION_ETC2;
+        let etc2 = Ktx2::parse(&etc1s(true).bytes(), features).unwrap();
+        assert_eq!(etc2.format, wgpu::TextureFormat::Etc2Rgba8UnormSrgb);
+        assert_eq!(etc2.levels[0].len(), 8 * 16);
+        // Base 83 with multiplier 3 and table 12 is within 1 of the alphas 111, 91, 73 and 53.
+        assert_eq!(etc2.levels[0][..2], [83, 3 << 4 | 12]);
+        assert_eq!(etc2.levels[0][8..11], [160, 80, 248]);
+        let bc3 = Ktx2::parse(&etc1s(true).bytes(), wgpu::Features::TEXTURE_COMPRESSION_BC);
+        let bc3 = bc3.unwrap();
+        assert_eq!(bc3.format, wgpu::TextureFormat::Bc3RgbaUnormSrgb);
+        assert_eq!(bc3.levels[0].len(), 8 * 16);
+        assert_eq!(bc3.levels[0][..2], [111, 53]);
+    }
+
+    #[test]
+    fn rejects_malformed_uastc_and_truncated_etc1s() {
+        // The 7 bit mode code 0x45 is reserved.
+        let mut block = [0; 16];
+        block[0] = 0x45;
+        let mut uastc = Container::new(0, 4, 4, &[&block]);
+        uastc.dfd = dfd(COLOR_MODEL_UASTC, TRANSFER_SRGB, &[0]);
+        assert!(matches!(parse(&uastc), Err(Ktx2Error::Uastc(_))));
+        uastc.levels = vec![(vec![0; 8], 8)];
+        assert!(matches!(
+            parse(&uastc),
+            Err(Ktx2Error::LevelSize { expected: 16, .. })
+        ));
+        uastc.supercompression = SUPERCOMPRESSION_BASIS_LZ;
+        assert!(matches!(
+            parse(&uastc),
+            Err(Ktx2Error::Supercompression(SUPERCOMPRESSION_BASIS_LZ))
+        ));
+
+        let mut etc1s = etc1s(false);
+        etc1s.global_data.truncate(30);
+        assert!(matches!(parse(&etc1s), Err(Ktx2Error::Truncated)));
+    }
+}
@@ -0,0 +1,738 @@
+//! Transcoder for the Basis Universal ETC1S payloads of KTX2 textures, supercompressed with
+//! BasisLZ. Every 4x4 block is an ETC1 block in differential mode without delta, so that it can
+//! be uploaded as ETC2, with its alpha re-encoded as EAC, re-encoded as BC1 or BC3, or expanded
+//! to RGBA8.
+
+use super::Ktx2Error;
+
+/// ETC1 intensity modifiers of each table, ordered from the darkest to the brightest.
+const MODIFIERS: [[i16; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+/// ETC1 pixel index of each modifier of `MODIFIERS`.
+const ETC1_INDICES: [u32; 4] = [3, 2, 0, 1];
+/// EAC modifiers of each table, scaled by the multiplier of a block.
+const EAC_MODIFIERS: [[i16; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+/// Order in which the sizes of the code length codes are stored.
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+const ENDPOINT_PRED_REPEAT: u32 = 256;
+const SELECTOR_RUN_VLC: u32 = 63;
+
+/// Picks what ETC1S is transcoded to: ETC2 keeps its color blocks as they are, with EAC blocks
+/// for the alpha, BC1 and BC3 are re-encoded, and RGBA8 is the fallback. Block compressed
+/// formats need a base level whose size is a multiple of the block size.
+pub fn target_format(
+    features: wgpu::Features,
+    alpha: bool,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureFormat {
+    let aligned = width.is_multiple_of(4) && height.is_multiple_of(4);
+    if aligned && features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2) {
+        if alpha {
+            wgpu::TextureFormat::Etc2Rgba8Unorm
+        } else {
+            wgpu::TextureFormat::Etc2Rgb8Unorm
+        }
+    } else if aligned && features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC) {
+        if alpha {
+            wgpu::TextureFormat::Bc3RgbaUnorm
+        } else {
+            wgpu::TextureFormat::Bc1RgbaUnorm
+        }
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+/// Transcodes the levels of an ETC1S texture to `format`, one of the formats `target_format`
+/// picks. `global_data` holds the codebooks and Huffman tables the slices of every level share.
+pub fn transcode(
+    global_data: &[u8],
+    levels: &[Vec<u8>],
+    width: u32,
+    height: u32,
+    alpha: bool,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<Vec<u8>>, Ktx2Error> {
+    let global = GlobalData::parse(global_data, levels.len())?;
+    // EAC blocks of the alpha endpoints, found once per green and intensity.
+    let mut eac_endpoints = [None; 256];
+    levels
+        .iter()
+        .zip(&global.images)
+        .enumerate()
+        .map(|(level, (data, image))| {
+            let width = (width >> level).max(1);
+            let height = (height >> level).max(1);
+            let blocks_x = width.div_ceil(4) as usize;
+            let blocks_y = height.div_ceil(4) as usize;
+            let slice = |(offset, len): (usize, usize)| {
+                data.get(offset..offset.saturating_add(len))
+                    .ok_or(Ktx2Error::Truncated)
+            };
+            if image.flags & 2 != 0 {
+                return Err(malformed("video frames aren't supported"));
+            }
+            let colors = global.decode_slice(slice(image.rgb)?, blocks_x, blocks_y)?;
+            let alphas = if alpha {
+                if image.alpha.1 == 0 {
+                    return Err(malformed("image without its alpha slice"));
+                }
+                Some(global.decode_slice(slice(image.alpha)?, blocks_x, blocks_y)?)
+            } else {
+                None
+            };
+
+            Ok(match format {
+                wgpu::TextureFormat::Etc2Rgb8Unorm | wgpu::TextureFormat::Etc2Rgba8Unorm => {
+                    let mut texels = Vec::with_capacity(colors.len() * 16);
+                    for (index, block) in colors.iter().enumerate() {
+                        if let Some(alphas) = &alphas {
+                            let alpha = &alphas[index];
+                            let endpoint = global.endpoint(alpha)?;
+                            let eac = *eac_endpoints[usize::from(endpoint.color[1]) << 3
+                                | usize::from(endpoint.intensity)]
+                            .get_or_insert_with(|| eac_endpoint(endpoint));
+                            texels.extend(eac_block(eac, global.selector(alpha)?));
+                        }
+                        texels.extend(etc1_block(global.endpoint(block)?, global.selector(block)?));
+                    }
+                    texels
+                }
+                wgpu::TextureFormat::Bc1RgbaUnorm | wgpu::TextureFormat::Bc3RgbaUnorm => {
+                    let mut texels = Vec::with_capacity(colors.len() * 16);
+                    for (index, block) in colors.iter().enumerate() {
+                        if let Some(alphas) = &alphas {
+                            let pixels = global.pixels(&alphas[index])?;
+                            texels.extend(bc4_block(pixels.map(|pixel| pixel[1])));
+                        }
+                        texels.extend(bc1_block(global.pixels(block)?));
+                    }
+                    texels
+                }
+                _ => {
+                    let mut texels = vec![0; width as usize * height as usize * 4];
+                    for (index, block) in colors.iter().enumerate() {
+                        let pixels = global.pixels(block)?;
+                        let alphas = match &alphas {
+                            Some(alphas) => Some(global.pixels(&alphas[index])?),
+                            None => None,
+                        };
+                        let (block_x, block_y) = (index % blocks_x * 4, index / blocks_x * 4);
+                        for (pixel, &[r, g, b]) in pixels.iter().enumerate() {
+                            let (x, y) = (block_x + pixel % 4, block_y + pixel / 4);
+                            if x < width as usize && y < height as usize {
+                                let a = alphas.map_or(255, |alphas| alphas[pixel][1]);
+                                let offset = (y * width as usize + x) * 4;
+                                texels[offset..offset + 4].copy_from_slice(&[r, g, b, a]);
+                            }
+                        }
+                    }
+                    texels
+                }
+            })
+        })
+        .collect()
+}
+
+/// Base color in 5 bits per channel and intensity table of an ETC1S block.
+#[derive(Clone, Copy, Default)]
+struct Endpoint {
+    color: [u8; 3],
+    intensity: u8,
+}
+
+/// Rows of 2-bit indices into the modifiers of a block, the first pixel in the low bits.
+type Selector = [u8; 4];
+
+/// Endpoint and selector codebook indices of a block.
+#[derive(Clone, Copy)]
+struct Block {
+    endpoint: usize,
+    selector: usize,
+}
+
+/// Byte ranges of the slices of an image within its level.
+struct ImageDesc {
+    flags: u32,
+    rgb: (usize, usize),
+    alpha: (usize, usize),
+}
+
+/// The BasisLZ global data: codebooks, Huffman tables and slice locations.
+struct GlobalData {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    endpoint_pred_model: Huffman,
+    delta_endpoint_model: Huffman,
+    selector_model: Huffman,
+    selector_history_run_model: Huffman,
+    selector_history_len: usize,
+    images: Vec<ImageDesc>,
+}
+
+impl GlobalData {
+    fn parse(bytes: &[u8], image_count: usize) -> Result<Self, Ktx2Error> {
+        let u16_at = |offset: usize| {
+            bytes
+                .get(offset..offset + 2)
+                .map(|bytes| usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+                .ok_or(Ktx2Error::Truncated)
+        };
+        let u32_at = |offset: usize| {
+            bytes
+                .get(offset..offset + 4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or(Ktx2Error::Truncated)
+        };
+        let endpoint_count = u16_at(0)?;
+        let selector_count = u16_at(2)?;
+        let [endpoints_len, selectors_len, tables_len, extended_len] =
+            [4, 8, 12, 16].map(|offset| u32_at(offset).map(|len| len as usize));
+        let images = (0..image_count)
+            .map(|image| {
+                let offset = 20 + image * 20;
+                let [flags, rgb_offset, rgb_len, alpha_offset, alpha_len] =
+                    [0, 4, 8, 12, 16].map(|field| u32_at(offset + field));
+                Ok(ImageDesc {
+                    flags: flags?,
+                    rgb: (rgb_offset? as usize, rgb_len? as usize),
+                    alpha: (alpha_offset? as usize, alpha_len? as usize),
+                })
+            })
+            .collect::<Result<Vec<_>, Ktx2Error>>()?;
+
+        let mut offset = 20 + image_count * 20;
+        let mut section = |len: usize| {
+            let section = bytes
+                .get(offset..offset.saturating_add(len))
+                .ok_or(Ktx2Error::Truncated);
+            offset = offset.saturating_add(len);
+            section
+        };
+        let endpoints = decode_endpoints(section(endpoints_len?)?, endpoint_count)?;
+        let selectors = decode_selectors(section(selectors_len?)?, selector_count)?;
+        let mut tables = Bits::new(section(tables_len?)?);
+        section(extended_len?)?;
+
+        Ok(Self {
+            endpoints,
+            selectors,
+            endpoint_pred_model: Huffman::read(&mut tables)?,
+            delta_endpoint_model: Huffman::read(&mut tables)?,
+            selector_model: Huffman::read(&mut tables)?,
+            selector_history_run_model: Huffman::read(&mut tables)?,
+            selector_history_len: tables.read(13) as usize,
+            images,
+        })
+    }
+
+    /// Decodes the codebook indices of every block of a slice, row by row.
+    fn decode_slice(
+        &self,
+        slice: &[u8],
+        blocks_x: usize,
+        blocks_y: usize,
+    ) -> Result<Vec<Block>, Ktx2Error> {
+        let mut bits = Bits::new(slice);
+        let endpoint_count = self.endpoints.len();
+        let selector_count = self.selectors.len();
+        let history_run_symbol = (selector_count + self.selector_history_len) as u32;
+        let mut history = SelectorHistory::new(self.selector_history_len);
+        let mut selector_run = 0;
+
+        // Endpoint predictions are coded once per 2x2 blocks, the lower row reading what the
+        // upper one left in `pending_preds`.
+        let mut pending_preds = vec![0; blocks_x];
+        let mut upper_endpoints = vec![0; blocks_x];
+        let mut preds = 0;
+        let mut previous_preds = 0;
+        let mut pred_repeat = 0;
+        let mut previous_endpoint = 0;
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+        for y in 0..blocks_y {
+            let mut endpoints = vec![0; blocks_x];
+            for x in 0..blocks_x {
+                if x % 2 == 0 {
+                    if y % 2 == 0 {
+                        if pred_repeat > 0 {
+                            pred_repeat -= 1;
+                            preds = previous_preds;
+                        } else {
+                            preds = self.endpoint_pred_model.decode(&mut bits)?;
+                            if preds == ENDPOINT_PRED_REPEAT {
+                                pred_repeat = bits.read_vlc(4)? + 2;
+                                preds = previous_preds;
+                            } else {
+                                previous_preds = preds;
+                            }
+                        }
+                        pending_preds[x] = preds >> 4;
+                    } else {
+                        preds = pending_preds[x];
+                    }
+                }
+
+                let endpoint = match preds & 3 {
+                    0 if x > 0 => previous_endpoint,
+                    1 if y > 0 => upper_endpoints[x],
+                    2 if x > 0 && y > 0 => upper_endpoints[x - 1],
+                    3 => {
+                        let delta = self.delta_endpoint_model.decode(&mut bits)? as usize;
+                        (previous_endpoint + delta) % endpoint_count.max(1)
+                    }
+                    _ => return Err(malformed("endpoint predicted from outside the slice")),
+                };
+                preds >>= 2;
+                endpoints[x] = endpoint;
+                previous_endpoint = endpoint;
+
+                let symbol = if selector_run > 0 {
+                    selector_run -= 1;
+                    selector_count as u32
+                } else {
+                    let symbol = self.selector_model.decode(&mut bits)?;
+                    if symbol == history_run_symbol {
+                        let run = self.selector_history_run_model.decode(&mut bits)?;
+                        selector_run = if run == SELECTOR_RUN_VLC {
+                            bits.read_vlc(7)? + 3
+                        } else {
+                            run + 3
+                        };
+                        if selector_run as usize > blocks_x * blocks_y {
+                            return Err(malformed("selector run past the slice"));
+                        }
+                        selector_run -= 1;
+                        selector_count as u32
+                    } else {
+                        symbol
+                    }
+                };
+                let selector = if symbol as usize >= selector_count {
+                    history.get(symbol as usize - selector_count)?
+                } else {
+                    history.add(symbol as usize);
+                    symbol as usize
+                };
+
+                blocks.push(Block { endpoint, selector });
+            }
+            upper_endpoints = endpoints;
+        }
+        Ok(blocks)
+    }
+
+    fn endpoint(&self, block: &Block) -> Result<Endpoint, Ktx2Error> {
+        self.endpoints
+            .get(block.endpoint)
+            .copied()
+            .ok_or_else(|| malformed("endpoint index out of the codebook"))
+    }
+
+    fn selector(&self, block: &Block) -> Result<Selector, Ktx2Error> {
+        self.selectors
+            .get(block.selector)
+            .copied()
+            .ok_or_else(|| malformed("selector index out of the codebook"))
+    }
+
+    /// Colors of the 16 pixels of a block, row by row.
+    fn pixels(&self, block: &Block) -> Result<[[u8; 3]; 16], Ktx2Error> {
+        let endpoint = self.endpoint(block)?;
+        let selector = self.selector(block)?;
+        let base = endpoint.color.map(|c| i16::from(c << 3 | c >> 2));
+        let modifiers = MODIFIERS[usize::from(endpoint.intensity)];
+        Ok(std::array::from_fn(|pixel| {
+            let index = selector[pixel / 4] >> (pixel % 4 * 2) & 3;
+            base.map(|c| (c + modifiers[usize::from(index)]).clamp(0, 255) as u8)
+        }))
+    }
+}
+
+fn decode_endpoints(bytes: &[u8], count: usize) -> Result<Vec<Endpoint>, Ktx2Error> {
+    let mut bits = Bits::new(bytes);
+    // Color deltas are coded with one of three models depending on the previous value.
+    let color_models = [
+        Huffman::read(&mut bits)?,
+        Huffman::read(&mut bits)?,
+        Huffman::read(&mut bits)?,
+    ];
+    let intensity_model = Huffman::read(&mut bits)?;
+    let grayscale = bits.read(1) == 1;
+
+    let mut previous = Endpoint {
+        color: [16; 3],
+        intensity: 0,
+    };
+    (0..count)
+        .map(|_| {
+            let delta = intensity_model.decode(&mut bits)?;
+            previous.intensity = ((u32::from(previous.intensity) + delta) & 7) as u8;
+            for channel in 0..if grayscale { 1 } else { 3 } {
+                let model = match previous.color[channel] {
+                    0..=9 => &color_models[0],
+                    10..=21 => &color_models[1],
+                    _ => &color_models[2],
+                };
+                let delta = model.decode(&mut bits)?;
+                previous.color[channel] = ((u32::from(previous.color[channel]) + delta) & 31) as u8;
+            }
+            if grayscale {
+                previous.color = [previous.color[0]; 3];
+            }
+            Ok(previous)
+        })
+        .collect()
+}
+
+fn decode_selectors(bytes: &[u8], count: usize) -> Result<Vec<Selector>, Ktx2Error> {
+    let mut bits = Bits::new(bytes);
+    if bits.read(1) == 1 || bits.read(1) == 1 {
+        return Err(malformed("global selector codebooks aren't supported"));
+    }
+    if bits.read(1) == 1 {
+        return Ok((0..count)
+            .map(|_| [0; 4].map(|_| bits.read(8) as u8))
+            .collect());
+    }
+
+    // Every selector after the first is coded as the XOR of its rows with the previous one.
+    let model = Huffman::read(&mut bits)?;
+    let mut previous = [0; 4].map(|_| bits.read(8) as u8);
+    let mut selectors = Vec::with_capacity(count);
+    if count > 0 {
+        selectors.push(previous);
+    }
+    for _ in 1..count {
+        for row in &mut previous {
+            *row ^= model.decode(&mut bits)? as u8;
+        }
+        selectors.push(previous);
+    }
+    Ok(selectors)
+}
+
+/// Recently used selectors, a used entry swaps places with the one halfway to the front.
+struct SelectorHistory {
+    selectors: Vec<usize>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(len: usize) -> Self {
+        Self {
+            selectors: vec![0; len],
+            rover: len / 2,
+        }
+    }
+
+    fn add(&mut self, selector: usize) {
+        if self.selectors.is_empty() {
+            return;
+        }
+        self.selectors[self.rover] = selector;
+        self.rover += 1;
+        if self.rover == self.selectors.len() {
+            self.rover = self.selectors.len() / 2;
+        }
+    }
+
+    fn get(&mut self, index: usize) -> Result<usize, Ktx2Error> {
+        let selector = *self
+            .selectors
+            .get(index)
+            .ok_or_else(|| malformed("selector history index out of range"))?;
+        self.selectors.swap(index / 2, index);
+        Ok(selector)
+    }
+}
+
+/// Canonical Huffman code, read one bit at a time from the most significant one.
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; 17],
+    /// Symbols ordered by code length, then value.
+    symbols: Vec<u32>,
+}
+
+impl Huffman {
+    fn new(code_lengths: &[u8]) -> Result<Self, Ktx2Error> {
+        let mut counts = [0; 17];
+        for &len in code_lengths {
+            *counts
+                .get_mut(usize::from(len))
+                .ok_or_else(|| malformed("Huffman code too long"))? += 1;
+        }
+        let symbols = (1..=16)
+            .flat_map(|len| {
+                code_lengths
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, &symbol_len)| symbol_len == len)
+                    .map(|(symbol, _)| symbol as u32)
+            })
+            .collect();
+        Ok(Self { counts, symbols })
+    }
+
+    /// Reads a table whose code lengths are themselves Huffman coded, with runs of zeros and
+    /// repeats of the previous length.
+    fn read(bits: &mut Bits<'_>) -> Result<Self, Ktx2Error> {
+        let symbol_count = bits.read(14) as usize;
+        if symbol_count == 0 {
+            return Self::new(&[]);
+        }
+        let code_length_count = bits.read(5) as usize;
+        if !(1..=CODE_LENGTH_ORDER.len()).contains(&code_length_count) {
+            return Err(malformed("invalid Huffman table"));
+        }
+        let mut code_length_lengths = [0; 21];
+        for &code in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[code] = bits.read(3) as u8;
+        }
+        let code_lengths = Self::new(&code_length_lengths)?;
+
+        let mut lengths = Vec::with_capacity(symbol_count);
+        while lengths.len() < symbol_count {
+            let (len, run) = match code_lengths.decode(bits)? {
+                len @ 0..=16 => (len as u8, 1),
+                17 => (0, bits.read(3) + 3),
+                18 => (0, bits.read(7) + 11),
+                code => {
+                    let run = if code == 19 {
+                        bits.read(2) + 3
+                    } else {
+                        bits.read(7) + 7
+                    };
+                    match lengths.last() {
+                        Some(&len) if len > 0 => (len, run),
+                        _ => return Err(malformed("Huffman code length repeated from nothing")),
+                    }
+                }
+            };
+            lengths.extend(std::iter::repeat_n(len, run as usize));
+        }
+        if lengths.len() > symbol_count {
+            return Err(malformed("Huffman code lengths past the symbols"));
+        }
+        Self::new(&lengths)
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Result<u32, Ktx2Error> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &self.counts[1..] {
+            code |= bits.read(1) as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(malformed("invalid Huffman code"))
+    }
+}
+
+/// Bitstream read least significant bit first, bits past the end read as zeros.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Bits<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn read(&mut self, len: u32) -> u32 {
+        (0..len).fold(0, |value, bit| {
+            let byte = self.bytes.get(self.position / 8).copied().unwrap_or(0);
+            let value = value | u32::from(byte >> (self.position % 8) & 1) << bit;
+            self.position += 1;
+            value
+        })
+    }
+
+    /// Variable length integer in chunks of `chunk_bits`, each followed by a continue bit.
+    fn read_vlc(&mut self, chunk_bits: u32) -> Result<u32, Ktx2Error> {
+        let mut value = 0;
+        for shift in (0..32).step_by(chunk_bits as usize) {
+            let chunk = self.read(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            if chunk >> chunk_bits == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("variable length integer too long"))
+    }
+}
+
+/// ETC1 block in differential mode with both subblocks sharing the endpoint.
+fn etc1_block(endpoint: Endpoint, selector: Selector) -> [u8; 8] {
+    let [r, g, b] = endpoint.color.map(|c| c << 3);
+    let intensity = endpoint.intensity;
+    // Pixels are stored column by column, the low index bits after the high ones.
+    let indices = (0..16).fold(0u32, |indices, pixel| {
+        let (x, y) = (pixel % 4, pixel / 4);
+        let index = ETC1_INDICES[usize::from(selector[y] >> (x * 2) & 3)];
+        let bit = x * 4 + y;
+        indices | (index >> 1) << (16 + bit) | (index & 1) << bit
+    });
+    let [i0, i1, i2, i3] = indices.to_be_bytes();
+    [r, g, b, intensity << 5 | intensity << 2 | 2, i0, i1, i2, i3]
+}
+
+/// Base, multiplier and table of an EAC block, and its index of each ETC1S modifier.
+type EacEndpoint = ([u8; 2], [u8; 4]);
+
+/// EAC block parameters closest to the alphas of an ETC1S endpoint, which its alpha slices
+/// store in the green channel.
+fn eac_endpoint(endpoint: Endpoint) -> EacEndpoint {
+    let green = endpoint.color[1];
+    let base = i16::from(green << 3 | green >> 2);
+    let alphas = MODIFIERS[usize::from(endpoint.intensity)].map(|m| (base + m).clamp(0, 255));
+    let mut best = (i32::MAX, ([0; 2], [0; 4]));
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        for multiplier in 1..16 {
+            // Every base putting one of the alphas exactly on one of the modifiers.
+            'base: for base in alphas
+                .iter()
+                .flat_map(|alpha| modifiers.map(|modifier| alpha - modifier * multiplier))
+            {
+                let base = base.clamp(0, 255);
+                let mut error = 0;
+                let mut indices = [0; 4];
+                for (alpha, index) in alphas.iter().zip(&mut indices) {
+                    let mut closest = i16::MAX;
+                    for (candidate, modifier) in modifiers.iter().enumerate() {
+                        let distance = ((base + modifier * multiplier).clamp(0, 255) - alpha).abs();
+                        if distance < closest {
+                            (closest, *index) = (distance, candidate as u8);
+                        }
+                    }
+                    error += i32::from(closest).pow(2);
+                    if error >= best.0 {
+                        continue 'base;
+                    }
+                }
+                let header = [base as u8, (multiplier as u8) << 4 | table as u8];
+                best = (error, (header, indices));
+                if error == 0 {
+                    return best.1;
+                }
+            }
+        }
+    }
+    best.1
+}
+
+/// EAC block of the alphas of an ETC2 RGBA8 block.
+fn eac_block((header, indices): EacEndpoint, selector: Selector) -> [u8; 8] {
+    // Pixels are stored column by column from the most significant bits.
+    let bits = (0..16).fold(0u64, |bits, pixel| {
+        let (x, y) = (pixel % 4, pixel / 4);
+        let index = indices[usize::from(selector[y] >> (x * 2) & 3)];
+        bits | u64::from(index) << (45 - (x * 4 + y) * 3)
+    });
+    let [_, _, i0, i1, i2, i3, i4, i5] = bits.to_be_bytes();
+    [header[0], header[1], i0, i1, i2, i3, i4, i5]
+}
+
+/// BC1 block spanning the darkest and brightest pixels, which an ETC1S block has on one line.
+fn bc1_block(pixels: [[u8; 3]; 16]) -> [u8; 8] {
+    let luma = |pixel: &&[u8; 3]| pixel.iter().map(|&c| u32::from(c)).sum::<u32>();
+    let brightest = pixels.iter().max_by_key(luma).unwrap();
+    let darkest = pixels.iter().min_by_key(luma).unwrap();
+    let pack = |[r, g, b]: [u8; 3]| {
+        let [r, g, b] =
+            [(r, 31), (g, 63), (b, 31)].map(|(c, max)| (u32::from(c) * max + 127) / 255);
+        (r << 11 | g << 5 | b) as u16
+    };
+    let unpack = |color: u16| {
+        let [r, g, b] = [(color >> 11) & 31, (color >> 5) & 63, color & 31].map(i32::from);
+        [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+    };
+    let (color0, color1) = (pack(*brightest), pack(*darkest));
+    let mut indices = 0u32;
+    if color0 != color1 {
+        let (color0, color1) = (unpack(color0), unpack(color1));
+        let palette = [
+            color0,
+            color1,
+            std::array::from_fn(|c| (2 * color0[c] + color1[c]) / 3),
+            std::array::from_fn(|c| (color0[c] + 2 * color1[c]) / 3),
+        ];
+        for (pixel, color) in pixels.iter().enumerate() {
+            let distance = |entry: &[i32; 3]| {
+                (0..3)
+                    .map(|c| (entry[c] - i32::from(color[c])).pow(2))
+                    .sum::<i32>()
+            };
+            let index = (0..4)
+                .min_by_key(|&index| distance(&palette[index]))
+                .unwrap();
+            indices |= (index as u32) << (pixel * 2);
+        }
+    }
+    let [c0, c1] = color0.to_le_bytes();
+    let [c2, c3] = color1.to_le_bytes();
+    let [i0, i1, i2, i3] = indices.to_le_bytes();
+    [c0, c1, c2, c3, i0, i1, i2, i3]
+}
+
+/// BC4 block of the alphas of a BC3 block, interpolating between the extremes.
+fn bc4_block(alphas: [u8; 16]) -> [u8; 8] {
+    let alpha0 = *alphas.iter().max().unwrap();
+    let alpha1 = *alphas.iter().min().unwrap();
+    let mut indices = 0u64;
+    if alpha0 != alpha1 {
+        let (a0, a1) = (u32::from(alpha0), u32::from(alpha1));
+        let palette: [u32; 8] = std::array::from_fn(|index| match index {
+            0 => a0,
+            1 => a1,
+            index => ((8 - index as u32) * a0 + (index as u32 - 1) * a1) / 7,
+        });
+        for (pixel, &alpha) in alphas.iter().enumerate() {
+            let index = (0..8)
+                .min_by_key(|&index| palette[index].abs_diff(u32::from(alpha)))
+                .unwrap();
+            indices |= (index as u64) << (pixel * 3);
+        }
+    }
+    let [i0, i1, i2, i3, i4, i5, ..] = indices.to_le_bytes();
+    [alpha0, alpha1, i0, i1, i2, i3, i4, i5]
+}
+
+const fn malformed(reason: &'static str) -> Ktx2Error {
+    Ktx2Error::Etc1s(reason)
+}
@@ -0,0 +1,618 @@
+//! Transcoder for the Basis Universal UASTC payloads of KTX2 textures. Every 4x4 block holds an
+//! ASTC 4x4 block in one of a few modes, with its partition, endpoints and weights laid out so
+//! that it can be repacked as ASTC without loss or decoded to RGBA8.
+
+use super::Ktx2Error;
+
+/// Layout of a UASTC block of a mode, the mode being coded in its lowest bits.
+struct Mode {
+    /// Code of the mode, read from the least significant bit, and its length.
+    code: u8,
+    code_len: u32,
+    /// Bits of the BC1, ETC1 and ETC2 hints between the mode and the ASTC data.
+    hint_bits: u32,
+    /// ASTC block mode, which gives the weight grid, range and plane count.
+    block_mode: u32,
+    /// ASTC color endpoint mode of every subset.
+    cem: u32,
+    components: usize,
+    endpoint_range: usize,
+    weight_bits: u32,
+    /// ASTC partition seed of each partition pattern, empty with a single subset.
+    seeds: &'static [u16],
+    subsets: usize,
+    plane: Plane,
+}
+
+/// Component the second weight plane of a mode applies to.
+#[derive(Clone, Copy, PartialEq)]
+enum Plane {
+    None,
+    /// Read from the block.
+    Stored,
+    Alpha,
+}
+
+const fn mode(
+    (code, code_len): (u8, u32),
+    hint_bits: u32,
+    (block_mode, cem): (u32, u32),
+    (endpoint_range, weight_bits): (usize, u32),
+    (seeds, subsets): (&'static [u16], usize),
+    plane: Plane,
+) -> Mode {
+    Mode {
+        code,
+        code_len,
+        hint_bits,
+        block_mode,
+        cem,
+        components: (cem as usize >> 2) + 1,
+        endpoint_range,
+        weight_bits,
+        seeds,
+        subsets,
+        plane,
+    }
+}
+
+/// The solid color mode, which holds an RGBA8 color instead of ASTC data.
+const SOLID: usize = 8;
+/// Code of the mode reserved for future use.
+const RESERVED_CODE: (u8, u32) = (0x45, 7);
+#[rustfmt::skip]
+const MODES: [Mode; 19] = [
+    mode((0x01, 4), 15, (0x242, 8), (19, 4), (&[], 1), Plane::None),
+    mode((0x35, 6), 15, (0x042, 8), (20, 2), (&[], 1), Plane::None),
+    mode((0x1D, 5), 15, (0x053, 8), (8, 3), (&SEEDS2, 2), Plane::None),
+    mode((0x03, 5), 15, (0x042, 8), (7, 2), (&SEEDS3, 3), Plane::None),
+    mode((0x13, 5), 15, (0x042, 8), (12, 2), (&SEEDS2, 2), Plane::None),
+    mode((0x0B, 5), 15, (0x053, 8), (20, 3), (&[], 1), Plane::None),
+    mode((0x1B, 5), 15, (0x442, 8), (18, 2), (&[], 1), Plane::Stored),
+    mode((0x07, 5), 15, (0x042, 8), (12, 2), (&SEEDS2_BC7_3, 2), Plane::None),
+    mode((0x17, 5), 0, (0, 0), (0, 0), (&[], 1), Plane::None),
+    mode((0x0F, 5), 23, (0x042, 12), (8, 2), (&SEEDS2, 2), Plane::None),
+    mode((0x02, 3), 17, (0x242, 12), (13, 4), (&[], 1), Plane::None),
+    mode((0x00, 2), 17, (0x442, 12), (13, 2), (&[], 1), Plane::Stored),
+    mode((0x06, 3), 17, (0x053, 12), (19, 3), (&[], 1), Plane::None),
+    mode((0x1F, 5), 23, (0x441, 12), (20, 1), (&[], 1), Plane::Stored),
+    mode((0x0D, 5), 23, (0x042, 12), (20, 2), (&[], 1), Plane::None),
+    mode((0x05, 7), 23, (0x242, 4), (20, 4), (&[], 1), Plane::None),
+    mode((0x15, 6), 23, (0x042, 4), (20, 2), (&SEEDS2, 2), Plane::None),
+    mode((0x25, 6), 23, (0x442, 4), (20, 2), (&[], 1), Plane::Alpha),
+    mode((0x09, 4), 15, (0x253, 8), (11, 5), (&[], 1), Plane::None),
+];
+
+/// ASTC seeds of the two subset patterns shared with BC7.
+#[rustfmt::skip]
+const SEEDS2: [u16; 30] = [
+    28, 20, 16, 29, 91, 9, 107, 72, 149, 204, 50, 114, 496, 17, 78, 39, 252, 828, 43, 156, 116,
+    210, 476, 273, 684, 359, 246, 195, 694, 524,
+];
+/// ASTC seeds of the three subset patterns shared with BC7.
+const SEEDS3: [u16; 11] = [260, 74, 32, 156, 183, 15, 745, 0, 335, 902, 254];
+/// ASTC seeds of the two subset patterns matching BC7 three subset ones with two merged.
+#[rustfmt::skip]
+const SEEDS2_BC7_3: [u16; 19] = [
+    36, 48, 61, 137, 161, 183, 226, 281, 302, 307, 479, 495, 593, 594, 605, 799, 812, 988, 993,
+];
+
+/// Bits and trits (3) or quints (5) of each ASTC integer sequence range, from 0-1 to 0-255.
+const RANGES: [(u32, u32); 21] = [
+    (1, 1),
+    (0, 3),
+    (2, 1),
+    (0, 5),
+    (1, 3),
+    (3, 1),
+    (1, 5),
+    (2, 3),
+    (4, 1),
+    (2, 5),
+    (3, 3),
+    (5, 1),
+    (3, 5),
+    (4, 3),
+    (6, 1),
+    (4, 5),
+    (5, 3),
+    (7, 1),
+    (5, 5),
+    (6, 3),
+    (8, 1),
+];
+/// Scale and bit pattern of the endpoint unquantization of the ranges with trits or quints,
+/// the pattern listing from its most significant bit which bit of the value goes there.
+const UNQUANTIZATION: [(u32, &[u8; 9]); 21] = [
+    (0, b"000000000"),
+    (0, b"000000000"),
+    (0, b"000000000"),
+    (0, b"000000000"),
+    (204, b"000000000"),
+    (0, b"000000000"),
+    (113, b"000000000"),
+    (93, b"b000b0bb0"),
+    (0, b"000000000"),
+    (54, b"b0000bb00"),
+    (44, b"cb000cbcb"),
+    (0, b"000000000"),
+    (26, b"cb0000cbc"),
+    (22, b"dcb000dcb"),
+    (0, b"000000000"),
+    (13, b"dcb0000dc"),
+    (11, b"edcb000ed"),
+    (0, b"000000000"),
+    (6, b"edcb0000e"),
+    (5, b"fedcb000f"),
+    (0, b"000000000"),
+];
+
+/// Bits encoding each group of 5 trits in an ASTC integer sequence.
+#[rustfmt::skip]
+const TRIT_ENCODING: [u8; 243] = [
+    0, 1, 2, 4, 5, 6, 8, 9, 10, 16, 17, 18, 20, 21, 22, 24, 25, 26, 3, 7, 11, 19, 23, 27, 12, 13,
+    14, 32, 33, 34, 36, 37, 38, 40, 41, 42, 48, 49, 50, 52, 53, 54, 56, 57, 58, 35, 39, 43, 51,
+    55, 59, 44, 45, 46, 64, 65, 66, 68, 69, 70, 72, 73, 74, 80, 81, 82, 84, 85, 86, 88, 89, 90,
+    67, 71, 75, 83, 87, 91, 76, 77, 78, 128, 129, 130, 132, 133, 134, 136, 137, 138, 144, 145,
+    146, 148, 149, 150, 152, 153, 154, 131, 135, 139, 147, 151, 155, 140, 141, 142, 160, 161, 162,
+    164, 165, 166, 168, 169, 170, 176, 177, 178, 180, 181, 182, 184, 185, 186, 163, 167, 171, 179,
+    183, 187, 172, 173, 174, 192, 193, 194, 196, 197, 198, 200, 201, 202, 208, 209, 210, 212, 213,
+    214, 216, 217, 218, 195, 199, 203, 211, 215, 219, 204, 205, 206, 96, 97, 98, 100, 101, 102,
+    104, 105, 106, 112, 113, 114, 116, 117, 118, 120, 121, 122, 99, 103, 107, 115, 119, 123, 108,
+    109, 110, 224, 225, 226, 228, 229, 230, 232, 233, 234, 240, 241, 242, 244, 245, 246, 248, 249,
+    250, 227, 231, 235, 243, 247, 251, 236, 237, 238, 28, 29, 30, 60, 61, 62, 92, 93, 94, 156,
+    157, 158, 188, 189, 190, 220, 221, 222, 31, 63, 95, 159, 191, 223, 124, 125, 126,
+];
+/// Bits encoding each group of 3 quints in an ASTC integer sequence.
+#[rustfmt::skip]
+const QUINT_ENCODING: [u8; 125] = [
+    0, 1, 2, 3, 4, 8, 9, 10, 11, 12, 16, 17, 18, 19, 20, 24, 25, 26, 27, 28, 5, 13, 21, 29, 6, 32,
+    33, 34, 35, 36, 40, 41, 42, 43, 44, 48, 49, 50, 51, 52, 56, 57, 58, 59, 60, 37, 45, 53, 61,
+    14, 64, 65, 66, 67, 68, 72, 73, 74, 75, 76, 80, 81, 82, 83, 84, 88, 89, 90, 91, 92, 69, 77,
+    85, 93, 22, 96, 97, 98, 99, 100, 104, 105, 106, 107, 108, 112, 113, 114, 115, 116, 120, 121,
+    122, 123, 124, 101, 109, 117, 125, 30, 102, 103, 70, 71, 38, 110, 111, 78, 79, 46, 118, 119,
+    86, 87, 54, 126, 127, 94, 95, 62, 39, 47, 55, 63, 31,
+];
+
+/// Picks what UASTC is transcoded to: ASTC 4x4 keeps its blocks, RGBA8 is the fallback. ASTC
+/// needs a base level whose size is a multiple of the block size.
+pub fn target_format(features: wgpu::Features, width: u32, height: u32) -> wgpu::TextureFormat {
+    let aligned = width.is_multiple_of(4) && height.is_multiple_of(4);
+    if aligned && features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC) {
+        wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        }
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+/// Transcodes the levels of a UASTC texture to `format`, one of the formats `target_format`
+/// picks.
+pub fn transcode(
+    levels: &[Vec<u8>],
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> Result<Vec<Vec<u8>>, Ktx2Error> {
+    levels
+        .iter()
+        .enumerate()
+        .map(|(level, data)| {
+            let width = (width >> level).max(1) as usize;
+            let height = (height >> level).max(1) as usize;
+            let blocks_x = width.div_ceil(4);
+            let expected = blocks_x * height.div_ceil(4) * 16;
+            if data.len() != expected {
+                return Err(Ktx2Error::LevelSize {
+                    level,
+                    len: data.len(),
+                    expected,
+                });
+            }
+            let blocks = data
+                .chunks_exact(16)
+                .map(|block| u128::from_le_bytes(block.try_into().unwrap()));
+
+            if let wgpu::TextureFormat::Astc { .. } = format {
+                let mut texels = Vec::with_capacity(data.len());
+                for block in blocks {
+                    texels.extend(astc_block(&Block::unpack(block)?));
+                }
+                return Ok(texels);
+            }
+            let mut texels = vec![0; width * height * 4];
+            for (index, block) in blocks.enumerate() {
+                let pixels = Block::unpack(block)?.pixels();
+                let (block_x, block_y) = (index % blocks_x * 4, index / blocks_x * 4);
+                for (pixel, rgba) in pixels.iter().enumerate() {
+                    let (x, y) = (block_x + pixel % 4, block_y + pixel / 4);
+                    if x < width && y < height {
+                        let offset = (y * width + x) * 4;
+                        texels[offset..offset + 4].copy_from_slice(rgba);
+                    }
+                }
+            }
+            Ok(texels)
+        })
+        .collect()
+}
+
+/// A UASTC block with its ASTC data unpacked.
+enum Block {
+    Solid([u8; 4]),
+    Astc {
+        mode: &'static Mode,
+        seed: u16,
+        /// Subset of every texel, row by row.
+        partition: [u8; 16],
+        /// Component of the second weight plane.
+        ccs: usize,
+        /// Integer sequence values of the low and high endpoints of each component of each
+        /// subset.
+        endpoints: [u8; 18],
+        /// Weights of every texel, both planes of a texel next to each other.
+        weights: [u8; 32],
+    },
+}
+
+impl Block {
+    fn unpack(bits: u128) -> Result<Self, Ktx2Error> {
+        let code = |(code, len): (u8, u32)| bits as u8 & ((1 << len) - 1) == code;
+        if code(RESERVED_CODE) {
+            return Err(malformed("reserved mode"));
+        }
+        let index = MODES
+            .iter()
+            .position(|mode| code((mode.code, mode.code_len)))
+            .ok_or_else(|| malformed("unknown mode"))?;
+        let mode = &MODES[index];
+        let mut position = mode.code_len;
+        let mut read = |len: u32| {
+            let value = bits.checked_shr(position).unwrap_or(0) as u32 & ((1 << len) - 1);
+            position += len;
+            value
+        };
+
+        if index == SOLID {
+            return Ok(Self::Solid([0; 4].map(|_| read(8) as u8)));
+        }
+        read(mode.hint_bits);
+        let (seed, partition) = if mode.seeds.is_empty() {
+            (0, [0; 16])
+        } else {
+            let pattern_bits = if mode.subsets == 3 { 4 } else { 5 };
+            let seed = *mode
+                .seeds
+                .get(read(pattern_bits) as usize)
+                .ok_or_else(|| malformed("partition pattern out of range"))?;
+            let partition = std::array::from_fn(|texel| {
+                astc_partition(seed, mode.subsets, texel % 4, texel / 4) as u8
+            });
+            (seed, partition)
+        };
+        let ccs = match mode.plane {
+            Plane::Stored => read(2) as usize,
+            _ => 3,
+        };
+
+        // Trits and quints of every endpoint are stored in groups before their bits.
+        let value_count = mode.components * 2 * mode.subsets;
+        let (value_bits, base) = RANGES[mode.endpoint_range];
+        let group_len = match base {
+            3 => 5,
+            5 => 3,
+            _ => value_count,
+        };
+        let groups = (0..value_count.div_ceil(group_len))
+            .map(|group| {
+                let len = (value_count - group * group_len).min(group_len) as u32;
+                let group_bits = match base {
+                    3 => [0, 2, 4, 5, 7, 8][len as usize],
+                    5 => [0, 3, 5, 7][len as usize],
+                    _ => 0,
+                };
+                read(group_bits)
+            })
+            .collect::<Vec<_>>();
+        let mut endpoints = [0; 18];
+        for (index, endpoint) in endpoints[..value_count].iter_mut().enumerate() {
+            let digit = groups[index / group_len] / base.pow((index % group_len) as u32) % base;
+            *endpoint = (read(value_bits) | digit << value_bits) as u8;
+        }
+
+        // The first weight of every subset drops its most significant bit, which is zero.
+        let planes = if mode.plane == Plane::None { 1 } else { 2 };
+        let mut weights = [0; 32];
+        for (index, weight) in weights[..16 * planes].iter_mut().enumerate() {
+            let texel = index / planes;
+            let subset = partition[texel];
+            let anchor = partition.iter().position(|&s| s == subset) == Some(texel);
+            *weight = read(mode.weight_bits - u32::from(anchor)) as u8;
+        }
+        if position > 128 {
+            return Err(malformed("block data past its end"));
+        }
+
+        Ok(Self::Astc {
+            mode,
+            seed,
+            partition,
+            ccs,
+            endpoints,
+            weights,
+        })
+    }
+
+    /// Colors of the 16 pixels of the block, row by row, interpolated the way an ASTC decoder
+    /// does for a linear texture.
+    fn pixels(&self) -> [[u8; 4]; 16] {
+        let (mode, partition, ccs, endpoints, weights) = match self {
+            Self::Solid(color) => return [*color; 16],
+            Self::Astc {
+                mode,
+                partition,
+                ccs,
+                endpoints,
+                weights,
+                ..
+            } => (mode, partition, *ccs, endpoints, weights),
+        };
+        let planes = if mode.plane == Plane::None { 1 } else { 2 };
+        // Low and high RGBA endpoints of each subset.
+        let colors: [[[u32; 4]; 2]; 3] = std::array::from_fn(|subset| {
+            std::array::from_fn(|end| {
+                let values = &endpoints[(subset * mode.components * 2).min(17)..];
+                let value = |component: usize| {
+                    let value = values.get(component * 2 + end).copied().unwrap_or(0);
+                    unquantize_endpoint(value, mode.endpoint_range)
+                };
+                match mode.components {
+                    2 => [value(0), value(0), value(0), value(1)],
+                    3 => [value(0), value(1), value(2), 255],
+                    _ => [value(0), value(1), value(2), value(3)],
+                }
+            })
+        });
+        std::array::from_fn(|texel| {
+            let [low, high] = colors[usize::from(partition[texel])];
+            std::array::from_fn(|component| {
+                let plane = usize::from(planes == 2 && component == ccs);
+                let weight = unquantize_weight(weights[texel * planes + plane], mode.weight_bits);
+                // Endpoints are expanded to 16 bits before interpolating.
+                let (low, high) = (low[component] * 257, high[component] * 257);
+                ((low * (64 - weight) + high * weight + 32) >> 6 >> 8) as u8
+            })
+        })
+    }
+}
+
+/// Repacks a UASTC block as an ASTC 4x4 block.
+fn astc_block(block: &Block) -> [u8; 16] {
+    let (mode, seed, partition, ccs, endpoints, weights) = match block {
+        Block::Solid(color) => {
+            // A void extent block covering the whole texture.
+            let color = color
+                .iter()
+                .rev()
+                .fold(0u128, |bits, &c| bits << 16 | (u128::from(c) * 257));
+            return (color << 64 | 0xFFFF_FFFF_FFFF_FDFC).to_le_bytes();
+        }
+        Block::Astc {
+            mode,
+            seed,
+            partition,
+            ccs,
+            endpoints,
+            weights,
+        } => (mode, *seed, partition, *ccs, *endpoints, *weights),
+    };
+    let planes = if mode.plane == Plane::None { 1 } else { 2 };
+    let value_count = mode.components * 2 * mode.subsets;
+    let (endpoints, weights) = disable_blue_contraction(mode, partition, endpoints, weights);
+
+    let mut bits = BitWriter::default();
+    bits.write(mode.block_mode, 11);
+    bits.write(mode.subsets as u32 - 1, 2);
+    if mode.subsets == 1 {
+        bits.write(mode.cem, 4);
+    } else {
+        // Every subset uses the same endpoint mode.
+        bits.write(u32::from(seed), 10);
+        bits.write(mode.cem << 2, 6);
+    }
+    write_integer_sequence(&mut bits, &endpoints[..value_count], mode.endpoint_range);
+
+    // Weights are stored from the last bit down, each with its bits reversed.
+    let weight_bits = mode.weight_bits;
+    let weight_count = 16 * planes as u32;
+    if planes == 2 {
+        bits.position = 128 - weight_count * weight_bits - 2;
+        bits.write(ccs as u32, 2);
+    }
+    for (index, &weight) in weights[..weight_count as usize].iter().enumerate() {
+        let reversed = u32::from(weight).reverse_bits() >> (32 - weight_bits);
+        bits.position = 128 - (index as u32 + 1) * weight_bits;
+        bits.write(reversed, weight_bits);
+    }
+    bits.bits.to_le_bytes()
+}
+
+/// Swaps the endpoints of the subsets an ASTC decoder would blue contract, which is when the
+/// high endpoint is darker than the low one, and inverts their weights to keep the colors.
+fn disable_blue_contraction(
+    mode: &Mode,
+    partition: &[u8; 16],
+    mut endpoints: [u8; 18],
+    mut weights: [u8; 32],
+) -> ([u8; 18], [u8; 32]) {
+    if mode.components < 3 {
+        return (endpoints, weights);
+    }
+    let planes = if mode.plane == Plane::None { 1 } else { 2 };
+    let weight_max = (1 << mode.weight_bits) - 1;
+    for subset in 0..mode.subsets {
+        let values = &mut endpoints[subset * mode.components * 2..][..mode.components * 2];
+        let sum = |end: usize| {
+            (0..3)
+                .map(|component| {
+                    unquantize_endpoint(values[component * 2 + end], mode.endpoint_range)
+                })
+                .sum::<u32>()
+        };
+        if sum(1) >= sum(0) {
+            continue;
+        }
+        for pair in values.chunks_exact_mut(2) {
+            pair.swap(0, 1);
+        }
+        for texel in (0..16).filter(|&texel| usize::from(partition[texel]) == subset) {
+            for weight in &mut weights[texel * planes..][..planes] {
+                *weight = weight_max - *weight;
+            }
+        }
+    }
+    (endpoints, weights)
+}
+
+/// Writes values of `range` as an ASTC integer sequence, the trits of 5 values or quints of 3
+/// interleaved with their bits.
+fn write_integer_sequence(bits: &mut BitWriter, values: &[u8], range: usize) {
+    let (value_bits, base) = RANGES[range];
+    let group_len = match base {
+        3 => 5,
+        5 => 3,
+        _ => 1,
+    };
+    for group in values.chunks(group_len) {
+        let digit = |index: usize| u32::from(group.get(index).copied().unwrap_or(0)) >> value_bits;
+        let low = |index: usize| {
+            u32::from(group.get(index).copied().unwrap_or(0)) & ((1 << value_bits) - 1)
+        };
+        match base {
+            3 => {
+                let trits = (0..5)
+                    .rev()
+                    .fold(0, |trits, index| trits * 3 + digit(index));
+                let t = u32::from(TRIT_ENCODING[trits as usize]);
+                for (index, t_bits) in [(0, 2), (1, 2), (2, 1), (3, 2), (4, 1)] {
+                    let shift = [0, 2, 4, 5, 7][index];
+                    bits.write(low(index), value_bits);
+                    bits.write(t >> shift & ((1 << t_bits) - 1), t_bits);
+                }
+            }
+            5 => {
+                let quints = (0..3)
+                    .rev()
+                    .fold(0, |quints, index| quints * 5 + digit(index));
+                let q = u32::from(QUINT_ENCODING[quints as usize]);
+                for (index, (shift, q_bits)) in [(0, 3), (3, 2), (5, 2)].into_iter().enumerate() {
+                    bits.write(low(index), value_bits);
+                    bits.write(q >> shift & ((1 << q_bits) - 1), q_bits);
+                }
+            }
+            _ => bits.write(low(0), value_bits),
+        }
+    }
+}
+
+/// Bits of an ASTC block written from the least significant one.
+#[derive(Default)]
+struct BitWriter {
+    bits: u128,
+    position: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, len: u32) {
+        let value = u128::from(value & ((1 << len) - 1));
+        self.bits |= value.checked_shl(self.position).unwrap_or(0);
+        self.position += len;
+    }
+}
+
+/// Endpoint value of an ASTC integer sequence value, from 0 to 255.
+fn unquantize_endpoint(value: u8, range: usize) -> u32 {
+    let (value_bits, base) = RANGES[range];
+    let value = u32::from(value);
+    if base == 1 {
+        // The bits are repeated down to the lowest one.
+        return (0..8)
+            .step_by(value_bits as usize)
+            .fold(0, |unquantized, shift| {
+                unquantized | (value << 8 >> value_bits) >> shift
+            })
+            & 0xFF;
+    }
+    let low = value & ((1 << value_bits) - 1);
+    let digit = value >> value_bits;
+    let (scale, pattern) = UNQUANTIZATION[range];
+    let a = if low & 1 == 1 { 0x1FF } else { 0 };
+    let b = pattern.iter().fold(0, |b, &bit| {
+        let bit = if bit == b'0' {
+            0
+        } else {
+            low >> (bit - b'a') & 1
+        };
+        b << 1 | bit
+    });
+    let unquantized = (digit * scale + b) ^ a;
+    a & 0x80 | unquantized >> 2
+}
+
+/// Weight of an ASTC weight value of `bits` bits, from 0 to 64.
+fn unquantize_weight(value: u8, bits: u32) -> u32 {
+    let value = u32::from(value);
+    let unquantized = (0..6).step_by(bits as usize).fold(0, |unquantized, shift| {
+        unquantized | (value << 6 >> bits) >> shift
+    }) & 0x3F;
+    if unquantized > 32 {
+        unquantized + 1
+    } else {
+        unquantized
+    }
+}
+
+/// Subset of a texel of a 4x4 block in the ASTC partition pattern of `seed`.
+fn astc_partition(seed: u16, subsets: usize, x: usize, y: usize) -> usize {
+    let (x, y) = (x as u32 * 2, y as u32 * 2);
+    let seed = u32::from(seed) + (subsets as u32 - 1) * 1024;
+    let mut rnum = seed;
+    rnum ^= rnum >> 15;
+    rnum = rnum.wrapping_sub(rnum << 17);
+    rnum = rnum.wrapping_add(rnum << 7);
+    rnum = rnum.wrapping_add(rnum << 4);
+    rnum ^= rnum >> 5;
+    rnum = rnum.wrapping_add(rnum << 16);
+    rnum ^= rnum >> 7;
+    rnum ^= rnum >> 3;
+    rnum ^= rnum << 6;
+    rnum ^= rnum >> 17;
+
+    let squared = |shift: u32| {
+        let value = rnum >> shift & 0xF;
+        value * value
+    };
+    let (sh1, sh2) = match (seed & 1 == 1, seed & 2 == 2) {
+        (true, high) => (if high { 4 } else { 5 }, if subsets == 3 { 6 } else { 5 }),
+        (false, high) => (if subsets == 3 { 6 } else { 5 }, if high { 4 } else { 5 }),
+    };
+    let a = (squared(0) >> sh1) * x + (squared(4) >> sh2) * y + (rnum >> 14);
+    let b = (squared(8) >> sh1) * x + (squared(12) >> sh2) * y + (rnum >> 10);
+    let c = (squared(16) >> sh1) * x + (squared(20) >> sh2) * y + (rnum >> 6);
+    let [a, b] = [a, b].map(|value| value & 0x3F);
+    let c = if subsets < 3 { 0 } else { c & 0x3F };
+
+    if a >= b && a >= c {
+        0
+    } else if b >= c {
+        1
+    } else {
+        2
+    }
+}
+
+const fn malformed(reason: &'static str) -> Ktx2Error {
+    Ktx2Error::Uastc(reason)
+}
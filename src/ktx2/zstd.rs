@@ -0,0 +1,773 @@
+//! Decoder for the Zstandard frames of supercompressed KTX2 levels, as specified by RFC 8878.
+//! Dictionaries aren't supported and content checksums aren't verified.
+
+use super::Ktx2Error;
+
+const MAGIC: u32 = 0xFD2F_B528;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+
+const LITERAL_LENGTH_DISTRIBUTION: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+const MATCH_LENGTH_DISTRIBUTION: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const OFFSET_DISTRIBUTION: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+/// Baseline and number of extra bits of every literal length code.
+const LITERAL_LENGTH_CODES: [(u32, u32); 36] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 0),
+    (12, 0),
+    (13, 0),
+    (14, 0),
+    (15, 0),
+    (16, 1),
+    (18, 1),
+    (20, 1),
+    (22, 1),
+    (24, 2),
+    (28, 2),
+    (32, 3),
+    (40, 3),
+    (48, 4),
+    (64, 6),
+    (128, 7),
+    (256, 8),
+    (512, 9),
+    (1024, 10),
+    (2048, 11),
+    (4096, 12),
+    (8192, 13),
+    (16384, 14),
+    (32768, 15),
+    (65536, 16),
+];
+/// Baseline and number of extra bits of the match length codes past the 32 without any.
+const MATCH_LENGTH_CODES: [(u32, u32); 21] = [
+    (35, 1),
+    (37, 1),
+    (39, 1),
+    (41, 1),
+    (43, 2),
+    (47, 2),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 5),
+    (131, 7),
+    (259, 8),
+    (515, 9),
+    (1027, 10),
+    (2051, 11),
+    (4099, 12),
+    (8195, 13),
+    (16387, 14),
+    (32771, 15),
+    (65539, 16),
+];
+
+/// Decompresses every frame of `bytes`, skippable frames are ignored.
+pub fn decompress(mut bytes: &[u8]) -> Result<Vec<u8>, Ktx2Error> {
+    let mut output = Vec::new();
+    while !bytes.is_empty() {
+        let magic = u32_le(take(&mut bytes, 4)?);
+        if magic & 0xFFFF_FFF0 == SKIPPABLE_MAGIC {
+            let size = u32_le(take(&mut bytes, 4)?);
+            take(&mut bytes, size as usize)?;
+        } else if magic == MAGIC {
+            Frame::default().decode(&mut bytes, &mut output)?;
+        } else {
+            return Err(malformed("unknown frame magic number"));
+        }
+    }
+    Ok(output)
+}
+
+/// State carried from one block of a frame to the next.
+struct Frame {
+    start: usize,
+    repeat_offsets: [usize; 3],
+    huffman: Option<HuffmanTable>,
+    literal_lengths: Option<FseTable>,
+    offsets: Option<FseTable>,
+    match_lengths: Option<FseTable>,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            repeat_offsets: [1, 4, 8],
+            huffman: None,
+            literal_lengths: None,
+            offsets: None,
+            match_lengths: None,
+        }
+    }
+}
+
+impl Frame {
+    fn decode(mut self, bytes: &mut &[u8], output: &mut Vec<u8>) -> Result<(), Ktx2Error> {
+        let descriptor = take(bytes, 1)?[0];
+        let single_segment = descriptor & 0x20 != 0;
+        let checksum = descriptor & 0x04 != 0;
+        if !single_segment {
+            // Window descriptor, the whole output stays addressable anyway.
+            take(bytes, 1)?;
+        }
+        let dictionary_id = take(bytes, [0, 1, 2, 4][usize::from(descriptor & 3)])?;
+        if dictionary_id.iter().any(|&byte| byte != 0) {
+            return Err(malformed("dictionaries aren't supported"));
+        }
+        let content_size_len = match descriptor >> 6 {
+            0 => usize::from(single_segment),
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        take(bytes, content_size_len)?;
+
+        self.start = output.len();
+        loop {
+            let header = take(bytes, 3)?;
+            let header =
+                u32::from(header[0]) | u32::from(header[1]) << 8 | u32::from(header[2]) << 16;
+            let size = (header >> 3) as usize;
+            match (header >> 1) & 3 {
+                0 => output.extend_from_slice(take(bytes, size)?),
+                1 => {
+                    let byte = take(bytes, 1)?[0];
+                    output.resize(output.len() + size, byte);
+                }
+                2 => self.block(take(bytes, size)?, output)?,
+                _ => return Err(malformed("reserved block type")),
+            }
+            if header & 1 != 0 {
+                break;
+            }
+        }
+        if checksum {
+            take(bytes, 4)?;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, mut block: &[u8], output: &mut Vec<u8>) -> Result<(), Ktx2Error> {
+        let literals = self.literals(&mut block)?;
+        let mut literals = literals.as_slice();
+        for sequence in self.sequences(block)? {
+            output.extend_from_slice(take(&mut literals, sequence.literal_length)?);
+            let offset = self.offset(sequence.offset_value, sequence.literal_length)?;
+            if offset == 0 || offset > output.len() - self.start {
+                return Err(malformed("match offset out of the frame"));
+            }
+            // Matches can overlap the bytes they produce.
+            for _ in 0..sequence.match_length {
+                output.push(output[output.len() - offset]);
+            }
+        }
+        output.extend_from_slice(literals);
+        Ok(())
+    }
+
+    fn literals(&mut self, block: &mut &[u8]) -> Result<Vec<u8>, Ktx2Error> {
+        let first = *block.first().ok_or_else(|| malformed("truncated block"))?;
+        let size_format = (first >> 2) & 3;
+        match first & 3 {
+            kind @ (0 | 1) => {
+                let header_len = match size_format {
+                    0 | 2 => 1,
+                    1 => 2,
+                    _ => 3,
+                };
+                let header = le(take(block, header_len)?);
+                let size = (header >> if header_len == 1 { 3 } else { 4 }) as usize;
+                if kind == 0 {
+                    Ok(take(block, size)?.to_vec())
+                } else {
+                    Ok(vec![take(block, 1)?[0]; size])
+                }
+            }
+            kind => {
+                let (header_len, size_bits, streams) = match size_format {
+                    0 => (3, 10, 1),
+                    1 => (3, 10, 4),
+                    2 => (4, 14, 4),
+                    _ => (5, 18, 4),
+                };
+                let header = le(take(block, header_len)?);
+                let mask = (1 << size_bits) - 1;
+                let regenerated = ((header >> 4) & mask) as usize;
+                let compressed = ((header >> (4 + size_bits)) & mask) as usize;
+                let mut data = take(block, compressed)?;
+                if kind == 2 {
+                    self.huffman = Some(HuffmanTable::read(&mut data)?);
+                }
+                let table = self
+                    .huffman
+                    .as_ref()
+                    .ok_or_else(|| malformed("literals reuse a missing Huffman table"))?;
+                if streams == 1 {
+                    return table.decode(data, regenerated);
+                }
+
+                let jump_table = take(&mut data, 6)?;
+                let stream_size = regenerated.div_ceil(4);
+                let last_size = regenerated
+                    .checked_sub(3 * stream_size)
+                    .ok_or_else(|| malformed("literals too short for four streams"))?;
+                let mut literals = Vec::with_capacity(regenerated);
+                for stream in 0..4 {
+                    let (len, count) = if stream < 3 {
+                        (usize::from(u16_le(&jump_table[stream * 2..])), stream_size)
+                    } else {
+                        (data.len(), last_size)
+                    };
+                    literals.extend(table.decode(take(&mut data, len)?, count)?);
+                }
+                Ok(literals)
+            }
+        }
+    }
+
+    fn sequences(&mut self, mut data: &[u8]) -> Result<Vec<Sequence>, Ktx2Error> {
+        let count = match take(&mut data, 1)?[0] {
+            0 => return Ok(Vec::new()),
+            byte @ 1..=127 => usize::from(byte),
+            byte @ 128..=254 => {
+                (usize::from(byte) - 128) << 8 | usize::from(take(&mut data, 1)?[0])
+            }
+            _ => usize::from(u16_le(take(&mut data, 2)?)) + 0x7F00,
+        };
+        let modes = take(&mut data, 1)?[0];
+        if modes & 3 != 0 {
+            return Err(malformed("reserved sequence compression mode bits"));
+        }
+        self.literal_lengths = Some(FseTable::for_mode(
+            modes >> 6,
+            &mut data,
+            (&LITERAL_LENGTH_DISTRIBUTION, 6),
+            (35, 9),
+            self.literal_lengths.take(),
+        )?);
+        self.offsets = Some(FseTable::for_mode(
+            (modes >> 4) & 3,
+            &mut data,
+            (&OFFSET_DISTRIBUTION, 5),
+            (31, 8),
+            self.offsets.take(),
+        )?);
+        self.match_lengths = Some(FseTable::for_mode(
+            (modes >> 2) & 3,
+            &mut data,
+            (&MATCH_LENGTH_DISTRIBUTION, 6),
+            (52, 9),
+            self.match_lengths.take(),
+        )?);
+
+        let mut bits = BackwardBits::new(data)?;
+        let [literal_lengths, offsets, match_lengths] =
+            [&self.literal_lengths, &self.offsets, &self.match_lengths]
+                .map(|table| FseState::new(table.as_ref().unwrap(), &mut bits));
+        let [mut literal_lengths, mut offsets, mut match_lengths] =
+            [literal_lengths?, offsets?, match_lengths?];
+        let mut sequences = Vec::with_capacity(count);
+        for index in 0..count {
+            let offset_code = u32::from(offsets.symbol());
+            let literal_length_code = usize::from(literal_lengths.symbol());
+            let match_length_code = usize::from(match_lengths.symbol());
+            if offset_code > 31 {
+                return Err(malformed("offset code out of range"));
+            }
+            let offset_value = (1 << offset_code) + bits.read(offset_code) as usize;
+            let (base, extra_bits) = match match_length_code {
+                code @ 0..=31 => (code as u32 + 3, 0),
+                code => *MATCH_LENGTH_CODES
+                    .get(code - 32)
+                    .ok_or_else(|| malformed("match length code out of range"))?,
+            };
+            let match_length = (base + bits.read(extra_bits) as u32) as usize;
+            let (base, extra_bits) = *LITERAL_LENGTH_CODES
+                .get(literal_length_code)
+                .ok_or_else(|| malformed("literal length code out of range"))?;
+            let literal_length = (base + bits.read(extra_bits) as u32) as usize;
+            sequences.push(Sequence {
+                literal_length,
+                offset_value,
+                match_length,
+            });
+            if index + 1 < count {
+                literal_lengths.update(&mut bits);
+                match_lengths.update(&mut bits);
+                offsets.update(&mut bits);
+            }
+        }
+        if bits.overflowed || bits.position != 0 {
+            return Err(malformed("sequences don't fill their bitstream"));
+        }
+        Ok(sequences)
+    }
+
+    /// Resolves an offset value against the repeated offsets, which it updates.
+    fn offset(&mut self, offset_value: usize, literal_length: usize) -> Result<usize, Ktx2Error> {
+        let [first, second, third] = self.repeat_offsets;
+        if offset_value > 3 {
+            self.repeat_offsets = [offset_value - 3, first, second];
+            return Ok(offset_value - 3);
+        }
+        // Without literals the first repeated offset would just extend the previous match.
+        let index = offset_value - usize::from(literal_length != 0);
+        self.repeat_offsets = match index {
+            0 => [first, second, third],
+            1 => [second, first, third],
+            2 => [third, first, second],
+            _ => [
+                first
+                    .checked_sub(1)
+                    .ok_or_else(|| malformed("repeated offset out of range"))?,
+                first,
+                second,
+            ],
+        };
+        Ok(self.repeat_offsets[0])
+    }
+}
+
+struct Sequence {
+    literal_length: usize,
+    offset_value: usize,
+    match_length: usize,
+}
+
+/// Prefix codes of the literals, indexed by the next `max_bits` bits of a stream.
+struct HuffmanTable {
+    max_bits: u32,
+    entries: Vec<(u8, u32)>,
+}
+
+impl HuffmanTable {
+    fn read(data: &mut &[u8]) -> Result<Self, Ktx2Error> {
+        let header = take(data, 1)?[0];
+        let mut weights = if header < 128 {
+            fse_weights(take(data, usize::from(header))?)?
+        } else {
+            let count = usize::from(header - 127);
+            let bytes = take(data, count.div_ceil(2))?;
+            (0..count)
+                .map(|index| {
+                    let byte = bytes[index / 2];
+                    if index % 2 == 0 {
+                        byte >> 4
+                    } else {
+                        byte & 15
+                    }
+                })
+                .collect()
+        };
+
+        // The weight of the last symbol is implied by the others completing a power of two.
+        let total = weights
+            .iter()
+            .filter(|&&weight| weight > 0)
+            .try_fold(0u32, |total, &weight| {
+                (weight <= 11).then(|| total + (1 << (weight - 1)))
+            })
+            .filter(|&total| total > 0)
+            .ok_or_else(|| malformed("invalid Huffman weights"))?;
+        let max_bits = 32 - total.leading_zeros();
+        let left = (1 << max_bits) - total;
+        if max_bits > 11 || !left.is_power_of_two() || weights.len() > 255 {
+            return Err(malformed("invalid Huffman weights"));
+        }
+        weights.push(left.trailing_zeros() as u8 + 1);
+
+        // Codes are assigned from the lowest weight up, in symbol order within a weight.
+        let mut entries = Vec::with_capacity(1 << max_bits);
+        for weight in 1..=max_bits {
+            weights
+                .iter()
+                .enumerate()
+                .filter(|(_, &symbol_weight)| u32::from(symbol_weight) == weight)
+                .for_each(|(symbol, _)| {
+                    let entry = (symbol as u8, max_bits + 1 - weight);
+                    entries.extend(std::iter::repeat_n(entry, 1 << (weight - 1)));
+                });
+        }
+        Ok(Self { max_bits, entries })
+    }
+
+    fn decode(&self, stream: &[u8], count: usize) -> Result<Vec<u8>, Ktx2Error> {
+        let mut bits = BackwardBits::new(stream)?;
+        let literals = (0..count)
+            .map(|_| {
+                let (symbol, len) = self.entries[bits.peek(self.max_bits) as usize];
+                bits.consume(len);
+                symbol
+            })
+            .collect();
+        if bits.overflowed || bits.position != 0 {
+            return Err(malformed("literals don't fill their stream"));
+        }
+        Ok(literals)
+    }
+}
+
+/// Huffman weights compressed with two interleaved FSE states.
+fn fse_weights(mut data: &[u8]) -> Result<Vec<u8>, Ktx2Error> {
+    let table = FseTable::read(&mut data, 255, 6)?;
+    let mut bits = BackwardBits::new(data)?;
+    let mut states = [
+        FseState::new(&table, &mut bits)?,
+        FseState::new(&table, &mut bits)?,
+    ];
+    let mut weights = Vec::new();
+    // Once the stream runs out the other state holds the last weight.
+    for state in (0..2).cycle() {
+        weights.push(states[state].symbol());
+        states[state].update(&mut bits);
+        if bits.overflowed {
+            weights.push(states[1 - state].symbol());
+            return Ok(weights);
+        }
+        if weights.len() > 255 {
+            break;
+        }
+    }
+    Err(malformed("too many Huffman weights"))
+}
+
+/// Finite state entropy decoding table, each state holds a symbol and the bits to read for the
+/// next state.
+struct FseTable {
+    accuracy_log: u32,
+    entries: Vec<FseEntry>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct FseEntry {
+    symbol: u8,
+    bits: u32,
+    base: usize,
+}
+
+impl FseTable {
+    fn for_mode(
+        mode: u8,
+        data: &mut &[u8],
+        (distribution, accuracy_log): (&[i16], u32),
+        (max_symbol, max_accuracy_log): (usize, u32),
+        previous: Option<Self>,
+    ) -> Result<Self, Ktx2Error> {
+        match mode {
+            0 => Ok(Self::new(distribution, accuracy_log)),
+            1 => Ok(Self {
+                accuracy_log: 0,
+                entries: vec![FseEntry {
+                    symbol: take(data, 1)?[0],
+                    ..FseEntry::default()
+                }],
+            }),
+            2 => Self::read(data, max_symbol, max_accuracy_log),
+            _ => previous.ok_or_else(|| malformed("sequences repeat a missing table")),
+        }
+    }
+
+    /// Reads a normalized distribution and builds its table.
+    fn read(data: &mut &[u8], max_symbol: usize, max_accuracy_log: u32) -> Result<Self, Ktx2Error> {
+        let mut bits = ForwardBits::new(data);
+        let accuracy_log = bits.read(4) + 5;
+        if accuracy_log > max_accuracy_log {
+            return Err(malformed("FSE accuracy log too large"));
+        }
+        let mut remaining = (1 << accuracy_log) + 1;
+        let mut threshold = 1 << accuracy_log;
+        let mut len = accuracy_log + 1;
+        let mut distribution = Vec::new();
+        while remaining > 1 {
+            if distribution.len() > max_symbol {
+                return Err(malformed("FSE distribution has too many symbols"));
+            }
+            // Values below `max` fit in one bit less.
+            let max = 2 * threshold - 1 - remaining;
+            let low = bits.peek(len - 1) as i32;
+            let value = if low < max {
+                bits.consume(len - 1);
+                low
+            } else {
+                let value = bits.read(len) as i32;
+                if value >= threshold {
+                    value - max
+                } else {
+                    value
+                }
+            };
+            let probability = value - 1;
+            remaining -= probability.abs();
+            distribution.push(probability as i16);
+            if probability == 0 {
+                loop {
+                    let repeat = bits.read(2);
+                    distribution.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat < 3 {
+                        break;
+                    }
+                }
+            }
+            while remaining < threshold {
+                len -= 1;
+                threshold >>= 1;
+            }
+        }
+        if remaining != 1 || distribution.len() > max_symbol + 1 {
+            return Err(malformed("invalid FSE distribution"));
+        }
+        *data = data
+            .get(bits.position.div_ceil(8)..)
+            .ok_or_else(|| malformed("truncated FSE distribution"))?;
+        Ok(Self::new(distribution.as_slice(), accuracy_log))
+    }
+
+    fn new(distribution: &[i16], accuracy_log: u32) -> Self {
+        let size = 1 << accuracy_log;
+        let mut entries = vec![FseEntry::default(); size];
+        let mut next_states = vec![0; distribution.len()];
+        // Symbols with a "less than one" probability take the last states.
+        let mut high = size;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            if probability == -1 {
+                high = high.saturating_sub(1);
+                entries[high].symbol = symbol as u8;
+                next_states[symbol] = 1;
+            } else {
+                next_states[symbol] = probability.max(0) as usize;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            for _ in 0..probability.max(0) {
+                entries[position].symbol = symbol as u8;
+                position = (position + step) & (size - 1);
+                while position >= high {
+                    position = (position + step) & (size - 1);
+                }
+            }
+        }
+        for entry in &mut entries {
+            let next_state = next_states[usize::from(entry.symbol)];
+            next_states[usize::from(entry.symbol)] += 1;
+            entry.bits = accuracy_log - next_state.ilog2();
+            entry.base = (next_state << entry.bits) - size;
+        }
+        Self {
+            accuracy_log,
+            entries,
+        }
+    }
+}
+
+struct FseState<'a> {
+    table: &'a FseTable,
+    state: usize,
+}
+
+impl<'a> FseState<'a> {
+    fn new(table: &'a FseTable, bits: &mut BackwardBits<'_>) -> Result<Self, Ktx2Error> {
+        let state = bits.read(table.accuracy_log) as usize;
+        if bits.overflowed {
+            return Err(malformed("truncated FSE state"));
+        }
+        Ok(Self { table, state })
+    }
+
+    fn symbol(&self) -> u8 {
+        self.table.entries[self.state].symbol
+    }
+
+    fn update(&mut self, bits: &mut BackwardBits<'_>) {
+        let entry = self.table.entries[self.state];
+        self.state = entry.base + bits.read(entry.bits) as usize;
+    }
+}
+
+/// Bitstream read from its first byte, least significant bits first.
+struct ForwardBits<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ForwardBits<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Bits past the end of the stream read as zeros.
+    fn peek(&self, len: u32) -> u32 {
+        (0..len).fold(0, |value, bit| {
+            let position = self.position + bit as usize;
+            let byte = self.bytes.get(position / 8).copied().unwrap_or(0);
+            value | u32::from(byte >> (position % 8) & 1) << bit
+        })
+    }
+
+    fn consume(&mut self, len: u32) {
+        self.position += len as usize;
+    }
+
+    fn read(&mut self, len: u32) -> u32 {
+        let value = self.peek(len);
+        self.consume(len);
+        value
+    }
+}
+
+/// Bitstream read from its last byte, whose highest set bit marks where the stream starts.
+struct BackwardBits<'a> {
+    bytes: &'a [u8],
+    /// Number of bits left to read.
+    position: usize,
+    /// Whether more bits were read than the stream holds.
+    overflowed: bool,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, Ktx2Error> {
+        match bytes.last() {
+            Some(&last) if last != 0 => Ok(Self {
+                bytes,
+                position: bytes.len() * 8 - last.leading_zeros() as usize - 1,
+                overflowed: false,
+            }),
+            _ => Err(malformed("bitstream without an end marker")),
+        }
+    }
+
+    /// The next `len` bits, the first one being the most significant. Bits before the start of
+    /// the stream read as zeros.
+    fn peek(&self, len: u32) -> u64 {
+        let len = len as usize;
+        let start = self.position.saturating_sub(len);
+        let value = self.bytes[start / 8..self.position.div_ceil(8)]
+            .iter()
+            .rev()
+            .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+        let available = self.position - start;
+        let value = (value >> (start % 8)) & ((1 << available) - 1);
+        value << (len - available)
+    }
+
+    fn consume(&mut self, len: u32) {
+        if len as usize > self.position {
+            self.overflowed = true;
+            self.position = 0;
+        } else {
+            self.position -= len as usize;
+        }
+    }
+
+    fn read(&mut self, len: u32) -> u64 {
+        let value = self.peek(len);
+        self.consume(len);
+        value
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Ktx2Error> {
+    if bytes.len() < len {
+        return Err(malformed("truncated frame"));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn le(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u64::from(byte))
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+const fn malformed(reason: &'static str) -> Ktx2Error {
+    Ktx2Error::Zstd(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lines of `text()` compressed by the zstd CLI at level 19: Huffman coded literals and
+    /// FSE coded sequences in a single compressed block.
+    const COMPRESSED: [u8; 199] = [
+        0x28, 0xB5, 0x2F, 0xFD, 0x64, 0x41, 0x05, 0xCD, 0x05, 0x00, 0x02, 0x0A, 0x18, 0x12, 0xB0,
+        0xA9, 0x03, 0x3C, 0x94, 0x01, 0xFA, 0x30, 0xA8, 0xB6, 0xB7, 0x4C, 0x29, 0x25, 0xA9, 0x9D,
+        0xE0, 0x40, 0x2D, 0xD6, 0x8A, 0x4A, 0xF8, 0xB9, 0x79, 0xFD, 0xF7, 0x7B, 0x7E, 0xA7, 0x0B,
+        0x3E, 0x36, 0x2E, 0xDF, 0x76, 0x4D, 0xCF, 0x64, 0xA1, 0xA7, 0xA6, 0xD5, 0xD7, 0x6D, 0xD9,
+        0x95, 0x2A, 0xF0, 0xD0, 0xB0, 0x78, 0x9A, 0x25, 0x39, 0x12, 0x85, 0x9D, 0x99, 0xD5, 0x3E,
+        0xAF, 0x3C, 0x26, 0x6E, 0xA4, 0xD1, 0x76, 0xE1, 0x3C, 0x9C, 0x71, 0x15, 0xFB, 0x9A, 0x4F,
+        0x4D, 0x0E, 0x40, 0x0C, 0x62, 0x21, 0x92, 0x60, 0x01, 0x32, 0x18, 0x47, 0x11, 0x50, 0x60,
+        0x10, 0x0A, 0x61, 0x04, 0x4B, 0xA8, 0x11, 0xD0, 0xF0, 0xDA, 0x7F, 0x03, 0x90, 0xE3, 0xC2,
+        0xE2, 0x11, 0x28, 0x28, 0x61, 0x4D, 0x11, 0xE1, 0x22, 0xDB, 0x07, 0xFD, 0x73, 0xD3, 0xCA,
+        0x50, 0xD5, 0xA6, 0xA3, 0x95, 0x94, 0xBA, 0x46, 0x67, 0x15, 0xA9, 0x39, 0xCD, 0x2E, 0x32,
+        0xA9, 0x98, 0x08, 0x26, 0x7C, 0x49, 0x5C, 0xA2, 0x96, 0x84, 0x25, 0xAE, 0xA4, 0xA8, 0xA4,
+        0x52, 0xCE, 0x3A, 0xF0, 0x47, 0x24, 0x35, 0xF7, 0xB7, 0x69, 0x9F, 0x29, 0x83, 0x1B, 0x69,
+        0xCD, 0x75, 0x88, 0x7D, 0xA7, 0xC1, 0xBD, 0x62, 0x33, 0xE5, 0x60, 0x55, 0xE0, 0x57, 0x01,
+        0x01, 0xDE, 0x86, 0xAE,
+    ];
+
+    fn text() -> Vec<u8> {
+        (0..64)
+            .map(|i| format!("level {i} holds {} texels\n", 1 << (i % 12)))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn decompresses_compressed_blocks() {
+        assert_eq!(decompress(&COMPRESSED).unwrap(), text());
+    }
+
+    #[test]
+    fn decompresses_raw_and_rle_blocks_after_a_skippable_frame() {
+        let frame = [
+            // Skippable frame of 2 bytes.
+            0x50, 0x2A, 0x4D, 0x18, 0x02, 0x00, 0x00, 0x00, 0xFF, 0xFF,
+            // Single segment frame of 7 bytes.
+            0x28, 0xB5, 0x2F, 0xFD, 0x20, 0x07,
+            // Raw block of 3 bytes, then the last block repeating 1 byte 4 times.
+            0x18, 0x00, 0x00, b'a', b'b', b'c', 0x23, 0x00, 0x00, b'x',
+        ];
+        assert_eq!(decompress(&frame).unwrap(), b"abcxxxx");
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        for len in [3, 6, 100, COMPRESSED.len() - 1] {
+            assert!(decompress(&COMPRESSED[..len]).is_err(), "{len} bytes");
+        }
+    }
+}
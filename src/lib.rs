@@ -1,6 +1,10 @@
+pub mod animation;
 pub mod camera;
 pub mod geometry;
+pub mod graph;
+pub mod ktx2;
 pub mod lighting;
 pub mod present;
 pub mod renderer;
 pub mod scene;
+pub mod shadow;
@@ -1,9 +1,115 @@
+use legion::{world::SubWorld, IntoQuery};
 use nalgebra::Matrix4;
 
-use crate::renderer::{RenderNode, RenderNodeBuilder};
+use crate::{
+    camera::{Projection, View},
+    geometry::GeometryPass,
+    graph::{Attachment, Pass},
+    renderer::{RenderNode, RenderNodeBuilder},
+    scene::{self, Scene},
+    shadow::ShadowPass,
+};
 
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub color: [f32; 4],
+    /// Illuminance in lux, the light shines along the -Z axis of its transform.
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            intensity: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct PointLight {
     pub color: [f32; 4],
+    /// Luminous intensity in candela.
+    pub intensity: f32,
+    /// Distance at which the light reaches zero, unbounded when `None`.
+    pub range: Option<f32>,
+    /// Renders a cube shadow map for the light, up to `ShadowPass::MAX_SHADOW_CASTERS` lights.
+    pub cast_shadows: bool,
+    /// Offset subtracted from the normalized distance to the light before the shadow map test.
+    pub shadow_bias: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            intensity: 1.0,
+            range: None,
+            cast_shadows: true,
+            shadow_bias: 0.005,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub color: [f32; 4],
+    /// Luminous intensity in candela, the cone points along the -Z axis of its transform.
+    pub intensity: f32,
+    /// Distance at which the light reaches zero, unbounded when `None`.
+    pub range: Option<f32>,
+    /// Angle in radians from the cone axis where the falloff starts.
+    pub inner_cone_angle: f32,
+    /// Angle in radians from the cone axis where the light reaches zero.
+    pub outer_cone_angle: f32,
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            intensity: 1.0,
+            range: None,
+            inner_cone_angle: 0.0,
+            outer_cone_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+/// Every light of the world along with its world transform, the light components first and
+/// then the lights of the imported scenes.
+pub fn lights(world: &SubWorld) -> Vec<(Light, Matrix4<f32>)> {
+    let mut lights = Vec::new();
+    lights.extend(
+        <(&DirectionalLight, &Matrix4<f32>)>::query()
+            .iter(world)
+            .map(|(light, transform_matrix)| (Light::Directional(*light), *transform_matrix)),
+    );
+    lights.extend(
+        <(&PointLight, &Matrix4<f32>)>::query()
+            .iter(world)
+            .map(|(light, transform_matrix)| (Light::Point(*light), *transform_matrix)),
+    );
+    lights.extend(
+        <(&SpotLight, &Matrix4<f32>)>::query()
+            .iter(world)
+            .map(|(light, transform_matrix)| (Light::Spot(*light), *transform_matrix)),
+    );
+    lights.extend(
+        <(&Scene, &Matrix4<f32>)>::query()
+            .iter(world)
+            .flat_map(|(scene, transform_matrix)| scene::light_instances(scene, transform_matrix))
+            .map(|(transform_matrix, light)| (light, transform_matrix)),
+    );
+
+    lights
 }
 
 #[repr(C)]
@@ -11,26 +117,91 @@ pub struct PointLight {
 struct LightSource {
     position: [f32; 4],
     color: [f32; 4],
+    direction: [f32; 4],
+    light_type: u32,
+    range: f32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    shadow_index: i32,
+    shadow_bias: f32,
+    shadow_far: f32,
+    _padding: f32,
+}
+
+impl LightSource {
+    const DIRECTIONAL: u32 = 0;
+    const POINT: u32 = 1;
+    const SPOT: u32 = 2;
+
+    fn new(light: &Light, transform_matrix: &Matrix4<f32>) -> Self {
+        let position = transform_matrix.column(3).into();
+        let direction = (-transform_matrix.column(2).xyz())
+            .normalize()
+            .push(0.0)
+            .into();
+        let premultiplied = |color: [f32; 4], intensity: f32| color.map(|c| c * intensity);
+
+        match light {
+            Light::Directional(light) => Self {
+                position,
+                color: premultiplied(light.color, light.intensity),
+                direction,
+                light_type: Self::DIRECTIONAL,
+                shadow_index: -1,
+                ..Default::default()
+            },
+            Light::Point(light) => Self {
+                position,
+                color: premultiplied(light.color, light.intensity),
+                direction,
+                light_type: Self::POINT,
+                range: light.range.unwrap_or_default(),
+                shadow_index: -1,
+                shadow_bias: light.shadow_bias,
+                shadow_far: light.range.unwrap_or(ShadowPass::SHADOW_FAR),
+                ..Default::default()
+            },
+            Light::Spot(light) => Self {
+                position,
+                color: premultiplied(light.color, light.intensity),
+                direction,
+                light_type: Self::SPOT,
+                range: light.range.unwrap_or_default(),
+                inner_cone_cos: light.inner_cone_angle.cos(),
+                outer_cone_cos: light.outer_cone_angle.cos(),
+                shadow_index: -1,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    position: [f32; 4],
+    /// Depth the geometry pass clears its depth attachment to, where nothing was drawn.
+    background_depth: f32,
+    _padding: [f32; 3],
 }
 
 pub struct LightingPass {
-    pub render_node: RenderNode,
     vertex_buffer: wgpu::Buffer,
     lights_bind_group_layout: wgpu::BindGroupLayout,
+    lights_bind_group: Option<wgpu::BindGroup>,
+    shadow_sampler: wgpu::Sampler,
 }
 
 impl LightingPass {
+    pub const COLOR: &'static str = "lbuffer_color";
+
     const LBUFFER_COLOR_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
 
     const QUAD: [f32; 12] = [
         -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
     ];
 
-    pub fn new(
-        device: &wgpu::Device,
-        size: wgpu::Extent3d,
-        input_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> Self {
+    pub fn new(device: &wgpu::Device) -> Self {
         let lights_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("lights"),
@@ -55,6 +226,22 @@ impl LightingPass {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
                 ],
             });
 
@@ -67,12 +254,53 @@ impl LightingPass {
             },
         );
 
-        let render_node = RenderNodeBuilder::default()
-            .with_name("lighting")
-            .with_color_attachment_format(Self::LBUFFER_COLOR_TEXTURE_FORMAT)
+        // Linear filtering compares the four nearest texels, blending the result across the
+        // edges of the cube faces too.
+        let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..wgpu::SamplerDescriptor::default()
+        });
+
+        Self {
+            vertex_buffer,
+            lights_bind_group_layout,
+            lights_bind_group: None,
+            shadow_sampler,
+        }
+    }
+}
+
+impl Pass for LightingPass {
+    fn name(&self) -> &'static str {
+        "lighting"
+    }
+
+    fn inputs(&self) -> Vec<&'static str> {
+        vec![
+            GeometryPass::POSITION,
+            GeometryPass::NORMAL,
+            GeometryPass::BASE_COLOR,
+            GeometryPass::MATERIAL,
+            GeometryPass::EMISSIVE,
+            GeometryPass::DEPTH,
+            ShadowPass::SHADOW_MAP,
+        ]
+    }
+
+    fn outputs(&self) -> Vec<Attachment> {
+        vec![Attachment::new(
+            Self::COLOR,
+            Self::LBUFFER_COLOR_TEXTURE_FORMAT,
+        )]
+    }
+
+    fn configure<'a>(&'a self, builder: RenderNodeBuilder<'a>) -> RenderNodeBuilder<'a> {
+        builder
             .with_shader_source(include_str!("lighting.wgsl").into())
-            .with_bind_group_layout(&input_bind_group_layout)
-            .with_bind_group_layout(&lights_bind_group_layout)
+            .with_bind_group_layout(&self.lights_bind_group_layout)
             .with_vertex_buffer_layout(wgpu::VertexBufferLayout {
                 array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
                 step_mode: wgpu::VertexStepMode::Vertex,
@@ -82,24 +310,13 @@ impl LightingPass {
                     format: wgpu::VertexFormat::Float32x2,
                 }],
             })
-            .build(&device, size);
-
-        Self {
-            render_node,
-            vertex_buffer,
-            lights_bind_group_layout,
-        }
     }
 
-    pub fn pass(
-        &self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        input_bind_group: &wgpu::BindGroup,
-        lights: Vec<(&PointLight, &Matrix4<f32>)>,
-    ) {
+    fn prepare(&mut self, device: &wgpu::Device, _queue: &wgpu::Queue, world: &SubWorld) {
+        let lights = lights(world);
         let lights_count = lights.len();
         if lights_count == 0 {
+            self.lights_bind_group = None;
             return;
         }
 
@@ -111,11 +328,32 @@ impl LightingPass {
                 usage: wgpu::BufferUsages::UNIFORM,
             },
         );
+        let (projection, view) = <(&Projection, &View)>::query().iter(world).next().unwrap();
+        let camera = CameraUniform {
+            position: view.position.to_homogeneous().into(),
+            background_depth: if projection.is_reverse_z() { 0.0 } else { 1.0 },
+            ..Default::default()
+        };
+        let camera_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("camera"),
+                contents: bytemuck::bytes_of(&camera),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let mut shadow_count = 0;
         let buffer_data = lights
             .iter()
-            .map(|(light, transform_matrix)| LightSource {
-                position: transform_matrix.column(3).into(),
-                color: light.color,
+            .map(|(light, transform_matrix)| {
+                let mut light_source = LightSource::new(light, transform_matrix);
+                if matches!(light, Light::Point(light) if light.cast_shadows)
+                    && shadow_count < ShadowPass::MAX_SHADOW_CASTERS as i32
+                {
+                    light_source.shadow_index = shadow_count;
+                    shadow_count += 1;
+                }
+                light_source
             })
             .collect::<Vec<_>>();
         let lights_buffer = wgpu::util::DeviceExt::create_buffer_init(
@@ -126,7 +364,7 @@ impl LightingPass {
                 usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             },
         );
-        let lights_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        self.lights_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("lights"),
             layout: &self.lights_bind_group_layout,
             entries: &[
@@ -138,13 +376,23 @@ impl LightingPass {
                     binding: 1,
                     resource: lights_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.shadow_sampler),
+                },
             ],
-        });
+        }));
+    }
 
-        let mut render_pass = self.render_node.begin_render_pass(encoder);
-        render_pass.set_bind_group(0, &input_bind_group, &[]);
-        render_pass.set_bind_group(1, &lights_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.draw(0..6, 0..1);
+    fn execute<'a>(&'a self, _node: &'a RenderNode, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(lights_bind_group) = &self.lights_bind_group {
+            render_pass.set_bind_group(1, lights_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
     }
 }
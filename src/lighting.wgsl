@@ -2,40 +2,146 @@
     return vec4<f32>(in_position, 0.0, 1.0);
 }
 
-@group(0) @binding(0) var g_buffer_position : texture_2d<f32>;
-@group(0) @binding(1) var g_buffer_normal   : texture_2d<f32>;
-@group(0) @binding(2) var g_buffer_albedo   : texture_2d<f32>;
-@group(0) @binding(3) var g_buffer_depth    : texture_depth_2d;
+@group(0) @binding(0) var g_buffer_position   : texture_2d<f32>;
+@group(0) @binding(1) var g_buffer_normal     : texture_2d<f32>;
+@group(0) @binding(2) var g_buffer_base_color : texture_2d<f32>;
+@group(0) @binding(3) var g_buffer_material   : texture_2d<f32>;
+@group(0) @binding(4) var g_buffer_emissive   : texture_2d<f32>;
+@group(0) @binding(5) var g_buffer_depth      : texture_2d<f32>;
+@group(0) @binding(6) var shadow_map          : texture_depth_cube_array;
 
 @group(1) @binding(0) var<uniform> light_count : u32;
 struct LightSource {
-    position : vec4<f32>,
-    color    : vec4<f32>,
+    position       : vec4<f32>,
+    // Color premultiplied by the intensity.
+    color          : vec4<f32>,
+    direction      : vec4<f32>,
+    light_type     : u32,
+    // Distance at which the light reaches zero, unbounded when zero.
+    range          : f32,
+    inner_cone_cos : f32,
+    outer_cone_cos : f32,
+    // Cube of the light in the shadow map, negative when it casts no shadows.
+    shadow_index   : i32,
+    shadow_bias    : f32,
+    shadow_far     : f32,
 }
 @group(1) @binding(1) var<storage, read> lights: array<LightSource>;
+struct Camera {
+    position         : vec4<f32>,
+    // Depth of the pixels no geometry was drawn to, 0 for reversed depth.
+    background_depth : f32,
+}
+@group(1) @binding(2) var<uniform> camera : Camera;
+@group(1) @binding(3) var shadow_sampler : sampler_comparison;
+
+const PI = 3.14159265359;
+
+const LIGHT_DIRECTIONAL = 0u;
+const LIGHT_POINT = 1u;
+const LIGHT_SPOT = 2u;
+
+// Trowbridge-Reitz GGX normal distribution function.
+fn distribution_ggx(n_dot_h : f32, roughness : f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+// Smith's method with the Schlick-GGX approximation for both view and light directions.
+fn geometry_smith(n_dot_v : f32, n_dot_l : f32, roughness : f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+fn fresnel_schlick(cos_theta : f32, f0 : vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Smooth falloff to zero at the light range recommended by KHR_lights_punctual.
+fn range_attenuation(dist : f32, range : f32) -> f32 {
+    if range <= 0.0 {
+        return 1.0;
+    }
+    return clamp(1.0 - pow(dist / range, 4.0), 0.0, 1.0);
+}
+
+fn spot_attenuation(light : LightSource, L : vec3<f32>) -> f32 {
+    let cos_angle = dot(light.direction.xyz, -L);
+    let scale = 1.0 / max(light.inner_cone_cos - light.outer_cone_cos, 0.001);
+    let attenuation = clamp((cos_angle - light.outer_cone_cos) * scale, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+
+// Fraction of the light's shadow map that sees the position, filtered across the cube faces by
+// the comparison sampler. GLSL has no explicit level lookup for cube array shadow samplers, the
+// shadow map has a single level anyway.
+fn shadow_factor(light : LightSource, position : vec3<f32>) -> f32 {
+    let light_to_world = position - light.position.xyz;
+    let depth = length(light_to_world) / light.shadow_far - light.shadow_bias;
+    return textureSampleCompare(
+        shadow_map, shadow_sampler, light_to_world, light.shadow_index, depth
+    );
+}
 
 @fragment fn fragment(@builtin(position) in_position : vec4<f32>) -> @location(0) vec4<f32> {
     let position = textureLoad(g_buffer_position, vec2<i32>(floor(in_position.xy)), 0).xyz;
     let normal = textureLoad(g_buffer_normal, vec2<i32>(floor(in_position.xy)), 0).xyz;
-    let albedo = textureLoad(g_buffer_albedo, vec2<i32>(floor(in_position.xy)), 0).rgb;
-    let depth = textureLoad(g_buffer_depth, vec2<i32>(floor(in_position.xy)), 0);
+    let albedo = textureLoad(g_buffer_base_color, vec2<i32>(floor(in_position.xy)), 0).rgb;
+    let material = textureLoad(g_buffer_material, vec2<i32>(floor(in_position.xy)), 0);
+    let emissive = textureLoad(g_buffer_emissive, vec2<i32>(floor(in_position.xy)), 0).rgb;
+    let depth = textureLoad(g_buffer_depth, vec2<i32>(floor(in_position.xy)), 0).r;
 
-    if depth >= 1.0 {
+    if depth == camera.background_depth {
         return vec4<f32>(albedo, 0.0);
     }
 
+    let occlusion = material.r;
+    let roughness = clamp(material.g, 0.04, 1.0);
+    let metallic = material.b;
+
     let N = normalize(normal);
+    let V = normalize(camera.position.xyz - position);
+    let n_dot_v = max(dot(N, V), 0.0001);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
 
     var surface_color = vec3<f32>(0.0, 0.0, 0.0);
     for (var i = 0u; i < light_count; i++) {
-        let world_to_light = lights[i].position.xyz - position;
-        let dist = length(world_to_light);
-        let wi = normalize(world_to_light);
+        var L : vec3<f32>;
+        var radiance = lights[i].color.rgb;
+        if lights[i].light_type == LIGHT_DIRECTIONAL {
+            L = -lights[i].direction.xyz;
+        } else {
+            let world_to_light = lights[i].position.xyz - position;
+            let dist = length(world_to_light);
+            L = normalize(world_to_light);
+            radiance *= range_attenuation(dist, lights[i].range) / pow(dist, 2.0);
+        }
+        if lights[i].light_type == LIGHT_SPOT {
+            radiance *= spot_attenuation(lights[i], L);
+        }
+        let H = normalize(V + L);
+
+        if lights[i].shadow_index >= 0 {
+            radiance *= shadow_factor(lights[i], position);
+        }
+        let n_dot_l = max(dot(N, L), 0.0);
+        let n_dot_h = max(dot(N, H), 0.0);
 
-        let radiance = lights[i].color.rgb * (1.0 / pow(dist, 2.0));
-        let n_dot_l = max(dot(N, wi), 0.0);
+        let D = distribution_ggx(n_dot_h, roughness);
+        let G = geometry_smith(n_dot_v, n_dot_l, roughness);
+        let F = fresnel_schlick(max(dot(H, V), 0.0), f0);
 
-        surface_color += albedo * radiance * n_dot_l;
+        let specular = D * G * F / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        let k_d = (vec3<f32>(1.0) - F) * (1.0 - metallic);
+
+        surface_color += (k_d * albedo / PI + specular) * radiance * n_dot_l;
     }
-    return vec4<f32>(surface_color, 1.0);
+
+    let ambient = vec3<f32>(0.03) * albedo * occlusion;
+    return vec4<f32>(ambient + surface_color + emissive, 1.0);
 }
@@ -4,7 +4,8 @@
 extern crate log;
 
 use std::{
-    thread,
+    path::{Path, PathBuf},
+    process, thread,
     time::{Duration, Instant},
 };
 
@@ -12,10 +13,11 @@ use clap::Parser;
 use legion::{system, world::SubWorld, IntoQuery, Resources, Schedule, World};
 use nalgebra::{Translation3, Vector3};
 use obscura::{
+    animation::{animate_system, AnimationPlayer, Playback},
     camera::{Projection, View},
     lighting::PointLight,
     renderer::{present_system, Renderer},
-    scene,
+    scene::{self, update_transforms_system, Imported},
 };
 use winit::{
     dpi::LogicalSize,
@@ -35,16 +37,29 @@ struct Cli {
 
     #[arg(long, default_value_t = 120)]
     frame_count: u32,
+
+    /// Render a single frame offscreen and write it to this image file instead of opening a window
+    #[arg(long)]
+    output: Option<PathBuf>,
 }
 
 fn main() {
     env_logger::init();
     let cli = Cli::parse();
+    if let Some(output) = cli.output {
+        render_to_file(cli.width, cli.height, &output);
+        return;
+    }
+
     let target_frame_time = Duration::from_secs_f64(1.0 / f64::from(cli.frame_count));
 
     let mut entity_world = World::default();
     let mut shared_resources = Resources::default();
-    let mut entity_scheduler = Schedule::builder().add_system(input_system()).build();
+    let mut entity_scheduler = Schedule::builder()
+        .add_system(input_system())
+        .add_system(animate_system())
+        .add_system(update_transforms_system())
+        .build();
     let mut render_scheduler = Schedule::builder().add_system(present_system()).build();
 
     let event_loop = EventLoop::new();
@@ -59,24 +74,12 @@ fn main() {
         .build(&event_loop)
         .unwrap();
     let renderer = Renderer::new(&window);
-
-    let camera = Projection::new(window.inner_size().width, window.inner_size().height);
-    entity_world.push((camera, View::default()));
-
-    let geometry = scene::import(&renderer.device, &renderer.queue, "res/BoxVertexColors.glb");
-    entity_world.push((
-        geometry,
-        Translation3::<f32>::new(-5.0, 0.0, -5.0).to_homogeneous(),
-    ));
-
-    let light = PointLight {
-        color: [100000.0, 0.0, 0.0, 0.0],
-    };
-    entity_world.push((
-        light,
-        Translation3::<f32>::new(0.0, 0.0, 10.0).to_homogeneous(),
-    ));
-
+    populate_world(
+        &mut entity_world,
+        &renderer,
+        window.inner_size().width,
+        window.inner_size().height,
+    );
     shared_resources.insert(renderer);
 
     let mut keyboard_keycode: Option<VirtualKeyCode> = None;
@@ -126,15 +129,73 @@ fn main() {
             }
             _ => (),
         }
-        let elapsed_frame_time = last_frame_time.elapsed();
-        if elapsed_frame_time < target_frame_time {
-            let sleep_duration = target_frame_time - elapsed_frame_time;
+        if let Some(sleep_duration) = target_frame_time.checked_sub(last_frame_time.elapsed()) {
             thread::sleep(sleep_duration);
         }
         last_frame_time = Instant::now();
     });
 }
 
+fn render_to_file(width: u32, height: u32, output: &Path) {
+    let mut renderer =
+        Renderer::headless(width, height).expect("no suitable graphics adapter found");
+    let mut entity_world = World::default();
+    populate_world(&mut entity_world, &renderer, width, height);
+
+    let image = renderer.render_to_image(&mut entity_world).unwrap();
+    image.save(output).unwrap();
+    info!("frame written to {}", output.display());
+}
+
+fn populate_world(entity_world: &mut World, renderer: &Renderer, width: u32, height: u32) {
+    let path = "res/BoxVertexColors.glb";
+    let Imported {
+        scene: geometry,
+        animations,
+    } = match scene::import(&renderer.device, &renderer.queue, &renderer.materials, path) {
+        Ok(imported) => imported,
+        Err(import_error) => {
+            error!("failed to import {path}: {import_error}");
+            process::exit(1);
+        }
+    };
+    let transform_matrix = Translation3::<f32>::new(-5.0, 0.0, -5.0).to_homogeneous();
+    let camera = scene::camera_instances(&geometry, &transform_matrix)
+        .first()
+        .map_or_else(
+            || (Projection::new(width, height), View::default()),
+            |(camera_matrix, projection)| {
+                (
+                    projection.with_viewport(width, height),
+                    View::from_transform(camera_matrix),
+                )
+            },
+        );
+    entity_world.push(camera);
+    let entity = entity_world.push((geometry, transform_matrix));
+    if let Some(clip) = animations.into_iter().next() {
+        entity_world
+            .entry(entity)
+            .unwrap()
+            .add_component(AnimationPlayer::new(Playback::new(clip)));
+    }
+
+    let light = PointLight {
+        color: [500.0, 0.0, 0.0, 0.0],
+        ..Default::default()
+    };
+    entity_world.push((
+        light,
+        Translation3::<f32>::new(0.0, 0.0, 10.0).to_homogeneous(),
+    ));
+}
+
+/// Moves the camera with the arrow and page keys, and turns it with the mouse while left control
+/// is held.
+///
+/// # Panics
+///
+/// Panics when the world has no camera.
 #[system]
 #[read_component(Projection)]
 #[write_component(View)]
@@ -156,8 +217,12 @@ pub fn input(
         }
         if *keyboard_keycode == VirtualKeyCode::LControl {
             if let Some((x, y)) = mouse_motion {
-                view.yaw += *x as f32 * step_factor * 0.01;
-                view.pitch += *y as f32 * step_factor * 0.01;
+                // Mouse deltas are a few pixels, well within `f32` precision.
+                #[allow(clippy::cast_possible_truncation)]
+                {
+                    view.yaw += *x as f32 * step_factor * 0.01;
+                    view.pitch += *y as f32 * step_factor * 0.01;
+                }
             }
         }
         let step = match *keyboard_keycode {
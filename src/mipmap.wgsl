@@ -0,0 +1,21 @@
+struct VertexOutput {
+    @builtin(position) position : vec4<f32>,
+    @location(0) tex_coord      : vec2<f32>,
+}
+
+// A single triangle covering the whole mip level.
+@vertex fn vertex(@builtin(vertex_index) vertex_index : u32) -> VertexOutput {
+    let position = vec2<f32>(f32(vertex_index % 2u) * 4.0 - 1.0, f32(vertex_index / 2u) * 4.0 - 1.0);
+
+    var out : VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.tex_coord = vec2<f32>(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+    return out;
+}
+
+@group(0) @binding(0) var source_texture : texture_2d<f32>;
+@group(0) @binding(1) var source_sampler : sampler;
+
+@fragment fn fragment(in : VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.tex_coord);
+}
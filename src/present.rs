@@ -1,8 +1,123 @@
-use crate::renderer::{RenderNode, RenderNodeBuilder};
+use image::RgbaImage;
+
+use crate::{
+    graph::{Attachment, Pass, RenderGraph},
+    lighting::LightingPass,
+    renderer::{RenderNode, RenderNodeBuilder},
+};
+
+pub enum PresentTarget {
+    Surface(wgpu::Surface),
+    Texture {
+        texture: wgpu::Texture,
+        readback_buffer: wgpu::Buffer,
+    },
+}
+
+impl PresentTarget {
+    pub fn offscreen(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("present"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("present readback"),
+            size: u64::from(Self::padded_bytes_per_row(size.width) * size.height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self::Texture {
+            texture,
+            readback_buffer,
+        }
+    }
+
+    /// Renders a frame with the graph into the target and submits it.
+    pub fn present(
+        &self,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        graph: &RenderGraph,
+    ) {
+        match self {
+            Self::Surface(surface) => {
+                let surface_texture = surface.get_current_texture().unwrap();
+                let view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                graph.execute(&mut encoder, &view);
+                queue.submit(std::iter::once(encoder.finish()));
+                surface_texture.present();
+            }
+            Self::Texture {
+                texture,
+                readback_buffer,
+            } => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                graph.execute(&mut encoder, &view);
+                encoder.copy_texture_to_buffer(
+                    texture.as_image_copy(),
+                    wgpu::ImageCopyBuffer {
+                        buffer: readback_buffer,
+                        layout: wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(Self::padded_bytes_per_row(texture.width())),
+                            rows_per_image: Some(texture.height()),
+                        },
+                    },
+                    texture.size(),
+                );
+                queue.submit(std::iter::once(encoder.finish()));
+            }
+        }
+    }
+
+    /// Reads the last presented frame back from an offscreen target, returns `None` when
+    /// presenting to a surface.
+    pub fn read_image(&self, device: &wgpu::Device) -> Option<RgbaImage> {
+        let Self::Texture {
+            texture,
+            readback_buffer,
+        } = self
+        else {
+            return None;
+        };
+        let buffer_slice = readback_buffer.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+
+        let unpadded_bytes_per_row = (4 * texture.width()) as usize;
+        let padded_bytes_per_row = Self::padded_bytes_per_row(texture.width()) as usize;
+        let pixels = buffer_slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row)
+            .flat_map(|row| &row[..unpadded_bytes_per_row])
+            .copied()
+            .collect::<Vec<_>>();
+        readback_buffer.unmap();
+
+        RgbaImage::from_raw(texture.width(), texture.height(), pixels)
+    }
+
+    fn padded_bytes_per_row(width: u32) -> u32 {
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        (4 * width).div_ceil(alignment) * alignment
+    }
+}
 
 pub struct PresentPass {
-    render_node: RenderNode,
-    surface: wgpu::Surface,
+    format: wgpu::TextureFormat,
     vertex_buffer: wgpu::Buffer,
 }
 
@@ -11,12 +126,7 @@ impl PresentPass {
         -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
     ];
 
-    pub fn new(
-        device: &wgpu::Device,
-        surface: wgpu::Surface,
-        config: wgpu::SurfaceConfiguration,
-        input_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> Self {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
         let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
             device,
             &wgpu::util::BufferInitDescriptor {
@@ -26,17 +136,29 @@ impl PresentPass {
             },
         );
 
-        let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
-            depth_or_array_layers: 1,
-        };
+        Self {
+            format,
+            vertex_buffer,
+        }
+    }
+}
+
+impl Pass for PresentPass {
+    fn name(&self) -> &'static str {
+        "present"
+    }
+
+    fn inputs(&self) -> Vec<&'static str> {
+        vec![LightingPass::COLOR]
+    }
 
-        let render_node = RenderNodeBuilder::default()
-            .with_name("present")
-            .with_color_attachment_format(config.format)
+    fn outputs(&self) -> Vec<Attachment> {
+        vec![Attachment::new(RenderGraph::BACKBUFFER, self.format)]
+    }
+
+    fn configure<'a>(&'a self, builder: RenderNodeBuilder<'a>) -> RenderNodeBuilder<'a> {
+        builder
             .with_shader_source(include_str!("present.wgsl").into())
-            .with_bind_group_layout(&input_bind_group_layout)
             .with_vertex_buffer_layout(wgpu::VertexBufferLayout {
                 array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
                 step_mode: wgpu::VertexStepMode::Vertex,
@@ -46,44 +168,10 @@ impl PresentPass {
                     format: wgpu::VertexFormat::Float32x2,
                 }],
             })
-            .build(&device, size);
-
-        Self {
-            render_node,
-            surface,
-            vertex_buffer,
-        }
     }
 
-    pub fn pass(
-        &self,
-        queue: &wgpu::Queue,
-        mut encoder: wgpu::CommandEncoder,
-        input_bind_group: &wgpu::BindGroup,
-    ) {
-        let present_texture = self.surface.get_current_texture().unwrap();
-        let present_texture_view = present_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &present_texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLUE),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            render_pass.set_pipeline(&self.render_node.render_pipeline);
-            render_pass.set_bind_group(0, &input_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.draw(0..6, 0..1);
-        }
-        queue.submit(std::iter::once(encoder.finish()));
-        present_texture.present();
+    fn execute<'a>(&'a self, _node: &'a RenderNode, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..6, 0..1);
     }
 }
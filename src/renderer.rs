@@ -1,14 +1,17 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::Arc};
 
-use legion::{system, world::SubWorld, IntoQuery};
+use image::RgbaImage;
+use legion::{system, world::SubWorld, World};
 use nalgebra::Matrix4;
 
 use crate::{
     camera::{Projection, View},
-    geometry::GeometryPass,
-    lighting::{LightingPass, PointLight},
-    present::PresentPass,
-    scene::Scene,
+    geometry::{Deformations, GeometryPass},
+    graph::RenderGraph,
+    lighting::{DirectionalLight, LightingPass, PointLight, SpotLight},
+    present::{PresentPass, PresentTarget},
+    scene::{self, MaterialRegistry, MipmapGenerator, MorphWeights, Scene},
+    shadow::ShadowPass,
 };
 
 pub struct RenderTarget {
@@ -17,57 +20,46 @@ pub struct RenderTarget {
 }
 
 impl RenderTarget {
+    /// Binds the attachments in order, usually as unfilterable float textures so that depth
+    /// attachments can be `textureLoad`ed on the GL backend too.
     pub fn new(
         device: &wgpu::Device,
         label: wgpu::Label,
-        color_attachments: &[wgpu::TextureView],
-        depth_stencil_attachment: &Option<wgpu::TextureView>,
+        attachments: &[(
+            &wgpu::TextureView,
+            wgpu::TextureViewDimension,
+            wgpu::TextureSampleType,
+        )],
     ) -> Self {
-        let mut bind_group_layout_entries = (0..color_attachments.len())
-            .map(|index| wgpu::BindGroupLayoutEntry {
-                binding: index as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
+        let bind_group_layout_entries = attachments
+            .iter()
+            .enumerate()
+            .map(
+                |(index, &(_, view_dimension, sample_type))| wgpu::BindGroupLayoutEntry {
+                    binding: index as u32,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                count: None,
-            })
+            )
             .collect::<Vec<_>>();
-
-        if depth_stencil_attachment.is_some() {
-            bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-                binding: color_attachments.len() as u32,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Depth,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            });
-        }
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label,
             entries: bind_group_layout_entries.as_slice(),
         });
 
-        let mut bind_group_entries = color_attachments
+        let bind_group_entries = attachments
             .iter()
             .enumerate()
-            .map(|(i, texture_view)| wgpu::BindGroupEntry {
+            .map(|(i, (texture_view, _, _))| wgpu::BindGroupEntry {
                 binding: i as u32,
                 resource: wgpu::BindingResource::TextureView(texture_view),
             })
             .collect::<Vec<_>>();
-
-        if let Some(texture_view) = depth_stencil_attachment {
-            bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: bind_group_entries.len() as u32,
-                resource: wgpu::BindingResource::TextureView(texture_view),
-            });
-        }
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label,
             layout: &bind_group_layout,
@@ -81,11 +73,14 @@ impl RenderTarget {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct RenderNodeBuilder<'a> {
     label: wgpu::Label<'a>,
     color_attachment_formats: Vec<wgpu::TextureFormat>,
     depth_stencil_format: Option<wgpu::TextureFormat>,
+    depth_compare: Option<wgpu::CompareFunction>,
+    front_face: Option<wgpu::FrontFace>,
+    topology: Option<wgpu::PrimitiveTopology>,
     shader_source: Cow<'a, str>,
     bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
     vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'a>>,
@@ -107,6 +102,21 @@ impl<'a> RenderNodeBuilder<'a> {
         self
     }
 
+    pub fn with_depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        self.depth_compare = Some(compare);
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.front_face = Some(front_face);
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = Some(topology);
+        self
+    }
+
     pub fn with_shader_source(mut self, source: Cow<'a, str>) -> Self {
         self.shader_source = source;
         self
@@ -122,62 +132,25 @@ impl<'a> RenderNodeBuilder<'a> {
         self
     }
 
-    pub fn build(self, device: &wgpu::Device, size: wgpu::Extent3d) -> RenderNode {
-        let color_views = self
-            .color_attachment_formats
-            .iter()
-            .map(|&format| {
-                let texture = device.create_texture(&wgpu::TextureDescriptor {
-                    label: self.label,
-                    size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format,
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                        | wgpu::TextureUsages::TEXTURE_BINDING,
-                    view_formats: &[],
-                });
-                texture.create_view(&wgpu::TextureViewDescriptor::default())
-            })
-            .collect::<Vec<_>>();
-        let depth_stencil_view = self.depth_stencil_format.map(|format| {
-            let depth_stencil_texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: self.label,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                    | wgpu::TextureUsages::TEXTURE_BINDING,
-                view_formats: &[],
-            });
-            depth_stencil_texture.create_view(&wgpu::TextureViewDescriptor::default())
-        });
-        let render_target = RenderTarget::new(
-            device,
-            self.label,
-            color_views.as_slice(),
-            &depth_stencil_view,
-        );
-        let render_pipeline = self.create_render_pipeline(device);
-
-        RenderNode {
-            render_target,
-            color_views,
-            depth_stencil_view,
-            render_pipeline,
-        }
+    pub fn with_input(mut self, input: &'a RenderTarget) -> Self {
+        self.bind_group_layouts.insert(0, &input.bind_group_layout);
+        self
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        self.create_render_pipeline(device)
     }
 
     fn create_render_pipeline(self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let topology = self
+            .topology
+            .unwrap_or(wgpu::PrimitiveTopology::TriangleList);
         let depth_stencil = self
             .depth_stencil_format
             .map(|format| wgpu::DepthStencilState {
                 format,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: self.depth_compare.unwrap_or(wgpu::CompareFunction::Less),
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             });
@@ -203,9 +176,9 @@ impl<'a> RenderNodeBuilder<'a> {
                 buffers: self.vertex_buffer_layouts.as_slice(),
             },
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
+                topology,
+                strip_index_format: topology.is_strip().then_some(wgpu::IndexFormat::Uint32),
+                front_face: self.front_face.unwrap_or(wgpu::FrontFace::Ccw),
                 cull_mode: Some(wgpu::Face::Back),
                 unclipped_depth: false,
                 polygon_mode: wgpu::PolygonMode::Fill,
@@ -239,21 +212,33 @@ impl<'a> RenderNodeBuilder<'a> {
 }
 
 pub struct RenderNode {
-    pub render_target: RenderTarget,
-    pub render_pipeline: wgpu::RenderPipeline,
-    color_views: Vec<wgpu::TextureView>,
-    depth_stencil_view: Option<wgpu::TextureView>,
+    pub input: Option<RenderTarget>,
+    /// One pipeline per topology the pass draws, the first one is bound when a render pass
+    /// begins.
+    pub render_pipelines: Vec<(wgpu::PrimitiveTopology, wgpu::RenderPipeline)>,
+    pub depth_compare: wgpu::CompareFunction,
 }
 
 impl RenderNode {
+    pub fn render_pipeline(
+        &self,
+        topology: wgpu::PrimitiveTopology,
+    ) -> Option<&wgpu::RenderPipeline> {
+        self.render_pipelines
+            .iter()
+            .find(|(pipeline_topology, _)| *pipeline_topology == topology)
+            .map(|(_, render_pipeline)| render_pipeline)
+    }
+
     pub fn begin_render_pass<'a>(
         &'a self,
         encoder: &'a mut wgpu::CommandEncoder,
-    ) -> wgpu::RenderPass {
-        let color_attachments = self
-            .color_views
+        color_views: &[&'a wgpu::TextureView],
+        depth_stencil_view: Option<&'a wgpu::TextureView>,
+    ) -> wgpu::RenderPass<'a> {
+        let color_attachments = color_views
             .iter()
-            .map(|target| {
+            .map(|&target| {
                 Some(wgpu::RenderPassColorAttachment {
                     view: target,
                     resolve_target: None,
@@ -265,16 +250,14 @@ impl RenderNode {
             })
             .collect::<Vec<_>>();
         let depth_stencil_attachment =
-            self.depth_stencil_view
-                .as_ref()
-                .map(|view| wgpu::RenderPassDepthStencilAttachment {
-                    view: &view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                });
+            depth_stencil_view.map(|view| wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.depth_clear_value()),
+                    store: true,
+                }),
+                stencil_ops: None,
+            });
 
         let render_pass_desc = wgpu::RenderPassDescriptor {
             label: None,
@@ -283,18 +266,31 @@ impl RenderNode {
         };
 
         let mut render_pass = encoder.begin_render_pass(&render_pass_desc);
-        render_pass.set_pipeline(&self.render_pipeline);
+        let (_, render_pipeline) = &self.render_pipelines[0];
+        render_pass.set_pipeline(render_pipeline);
+        if let Some(input) = &self.input {
+            render_pass.set_bind_group(0, &input.bind_group, &[]);
+        }
 
         render_pass
     }
+
+    /// Farthest depth for the depth test of the node, 0 when it passes greater values.
+    pub const fn depth_clear_value(&self) -> f32 {
+        match self.depth_compare {
+            wgpu::CompareFunction::Greater | wgpu::CompareFunction::GreaterEqual => 0.0,
+            _ => 1.0,
+        }
+    }
 }
 
 pub struct Renderer {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    geometry_pass: GeometryPass,
-    lighting_pass: LightingPass,
-    present_pass: PresentPass,
+    pub graph: RenderGraph,
+    pub materials: MaterialRegistry,
+    deformations: Arc<Deformations>,
+    present_target: PresentTarget,
 }
 
 impl Renderer {
@@ -310,15 +306,7 @@ impl Renderer {
             compatible_surface: Some(&surface),
         }))
         .unwrap();
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .unwrap();
+        let (device, queue) = Self::request_device(&adapter);
         let format = surface
             .get_capabilities(&adapter)
             .formats
@@ -342,25 +330,113 @@ impl Renderer {
             height: config.height,
             depth_or_array_layers: 1,
         };
-        let geometry_pass = GeometryPass::new(&device, size);
-        let lighting_pass = LightingPass::new(
-            &device,
+        Self::with_present_target(
+            device,
+            queue,
+            PresentTarget::Surface(surface),
+            config.format,
             size,
-            &geometry_pass.render_node.render_target.bind_group_layout,
-        );
-        let present_pass = PresentPass::new(
-            &device,
-            surface,
-            config,
-            &lighting_pass.render_node.render_target.bind_group_layout,
-        );
+            &adapter,
+        )
+    }
+
+    /// Creates a renderer without a window that presents into an offscreen texture, the
+    /// adapter backend can be selected with the `WGPU_BACKEND` environment variable.
+    pub fn headless(width: u32, height: u32) -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+        });
+        let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+            &instance, None,
+        ))?;
+        let (device, queue) = Self::request_device(&adapter);
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let present_target = PresentTarget::offscreen(&device, format, size);
+
+        Some(Self::with_present_target(
+            device,
+            queue,
+            present_target,
+            format,
+            size,
+            &adapter,
+        ))
+    }
+
+    pub fn render(&mut self, world: &SubWorld) {
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("present"),
+            });
+        self.deformations
+            .update(&self.device, &self.queue, &scene::meshes(world));
+        self.graph.prepare(&self.device, &self.queue, world);
+        self.present_target
+            .present(&self.queue, encoder, &self.graph);
+    }
+
+    /// Renders a frame and reads it back, returns `None` unless the renderer is headless.
+    pub fn render_to_image(&mut self, world: &mut World) -> Option<RgbaImage> {
+        self.render(&SubWorld::from(world));
+        self.present_target.read_image(&self.device)
+    }
+
+    /// Enables every block compressed texture format the adapter supports, so that KTX2
+    /// textures can be uploaded without decompressing them.
+    fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+        let texture_compression = adapter.features()
+            & (wgpu::Features::TEXTURE_COMPRESSION_BC
+                | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                | wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+        pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                    | texture_compression,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .unwrap()
+    }
+
+    fn with_present_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        present_target: PresentTarget,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        adapter: &wgpu::Adapter,
+    ) -> Self {
+        let downlevel_flags = adapter.get_downlevel_capabilities().flags;
+        let materials = MaterialRegistry::new(&device, &queue)
+            .with_srgb_views(downlevel_flags.contains(wgpu::DownlevelFlags::VIEW_FORMATS))
+            .with_mipmap_generator(
+                MipmapGenerator::new(&device)
+                    .with_level_copies(adapter.get_info().backend == wgpu::Backend::Gl),
+            );
+        let deformations = Arc::new(Deformations::new(&device));
+        let mut graph = RenderGraph::new(size);
+        graph.add_pass(GeometryPass::new(&device, &materials, deformations.clone()));
+        graph.add_pass(ShadowPass::new(&device, &materials, deformations.clone()));
+        graph.add_pass(LightingPass::new(&device));
+        graph.add_pass(PresentPass::new(&device, format));
 
         Self {
             device,
             queue,
-            geometry_pass,
-            lighting_pass,
-            present_pass,
+            graph,
+            materials,
+            deformations,
+            present_target,
         }
     }
 }
@@ -369,40 +445,11 @@ impl Renderer {
 #[read_component(Projection)]
 #[read_component(View)]
 #[read_component(Scene)]
+#[read_component(MorphWeights)]
+#[read_component(DirectionalLight)]
 #[read_component(PointLight)]
+#[read_component(SpotLight)]
 #[read_component(Matrix4<f32>)]
 pub fn present(world: &mut SubWorld, #[resource] renderer: &mut Renderer) {
-    let mut encoder = renderer
-        .device
-        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("present"),
-        });
-    let (projection, view) = <(&Projection, &View)>::query().iter(world).next().unwrap();
-
-    let geometries = <(&Scene, &Matrix4<f32>)>::query()
-        .iter(world)
-        .collect::<Vec<_>>();
-    renderer.geometry_pass.pass(
-        &renderer.queue,
-        &mut encoder,
-        projection.as_matrix(),
-        view.as_matrix(),
-        geometries,
-    );
-
-    let lights = <(&PointLight, &Matrix4<f32>)>::query()
-        .iter(world)
-        .collect::<Vec<_>>();
-    renderer.lighting_pass.pass(
-        &renderer.device,
-        &mut encoder,
-        &renderer.geometry_pass.render_node.render_target.bind_group,
-        lights,
-    );
-
-    renderer.present_pass.pass(
-        &renderer.queue,
-        encoder,
-        &renderer.lighting_pass.render_node.render_target.bind_group,
-    );
+    renderer.render(world);
 }
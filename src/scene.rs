@@ -1,141 +1,285 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
 
-use image::{DynamicImage, ImageBuffer, Rgb, Rgba, RgbaImage};
-use nalgebra::Matrix4;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use legion::{system, world::SubWorld, IntoQuery};
+use nalgebra::{Matrix3, Matrix4, Quaternion, UnitQuaternion, Vector3};
+use petgraph::{stable_graph::NodeIndex, visit::Dfs};
 
-use crate::geometry::VertexAttribute;
+use crate::{
+    animation::{self, AnimationClip, Pose},
+    camera::Projection,
+    geometry::VertexAttribute,
+    ktx2::{Ktx2, Ktx2Error},
+    lighting::{DirectionalLight, Light, PointLight, SpotLight},
+    renderer::RenderNodeBuilder,
+};
 
 pub type Scene = petgraph::stable_graph::StableGraph<Node, ()>;
 
-pub fn import<P>(device: &wgpu::Device, queue: &wgpu::Queue, path: P) -> Scene
+/// A scene imported from a glTF file along with the file's animations, whose channels target
+/// the nodes of the scene.
+pub struct Imported {
+    pub scene: Scene,
+    pub animations: Vec<Arc<AnimationClip>>,
+}
+
+/// Which scene of a glTF file to import.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SceneSelection<'a> {
+    /// The file's default scene, or the first one when it doesn't name one. Files without any
+    /// scene import every node.
+    #[default]
+    Default,
+    Index(usize),
+    Name(&'a str),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("failed to read glTF file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse glTF file: {0}")]
+    Parse(gltf::Error),
+    #[error("{location} uses unsupported {feature}")]
+    Unsupported { location: String, feature: String },
+    #[error("{location} has a malformed {accessor} accessor")]
+    MalformedAccessor {
+        location: String,
+        accessor: &'static str,
+    },
+    #[error("no scene {0} in glTF file")]
+    MissingScene(String),
+    #[error("failed to read KTX2 texture: {0}")]
+    Ktx2(#[from] Ktx2Error),
+}
+
+impl From<gltf::Error> for ImportError {
+    fn from(error: gltf::Error) -> Self {
+        match error {
+            gltf::Error::Io(error) => Self::Io(error),
+            error => Self::Parse(error),
+        }
+    }
+}
+
+pub fn import<P>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    registry: &MaterialRegistry,
+    path: P,
+) -> Result<Imported, ImportError>
+where
+    P: AsRef<Path>,
+{
+    import_scene(device, queue, registry, path, SceneSelection::Default)
+}
+
+/// Imports a scene of a glTF file and its animations, graph nodes keep the indices of the glTF
+/// nodes and the nodes outside the scene are left out. Textures that fail to load are replaced
+/// by the material defaults with a warning.
+pub fn import_scene<P>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    registry: &MaterialRegistry,
+    path: P,
+    selection: SceneSelection<'_>,
+) -> Result<Imported, ImportError>
 where
     P: AsRef<Path>,
 {
-    let (doc, buffers, images) = gltf::import(path).unwrap();
+    let (doc, buffers, root) = open(path.as_ref())?;
+    let basisu_sources = basisu_sources(&root);
+    let images = doc
+        .images()
+        .map(|image| {
+            if basisu_sources.contains(&Some(image.index())) {
+                return None;
+            }
+            gltf::image::Data::from_source(image.source(), path.as_ref().parent(), &buffers)
+                .map_err(|error| {
+                    log::warn!("image {} failed to load: {error}", image.index());
+                })
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    // Colors are stored in sRGB and data like normals in linear, a texture used for both is
+    // uploaded once per color space.
+    let srgb = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let linear = wgpu::TextureFormat::Rgba8Unorm;
+    let mut formats = vec![Vec::new(); doc.textures().len()];
+    doc.materials().for_each(|m| {
+        let pbr_metallic_roughness = m.pbr_metallic_roughness();
+        let colors = [
+            pbr_metallic_roughness
+                .base_color_texture()
+                .map(|tex| tex.texture()),
+            m.emissive_texture().map(|tex| tex.texture()),
+        ]
+        .map(|texture| (texture, srgb));
+        let data = [
+            pbr_metallic_roughness
+                .metallic_roughness_texture()
+                .map(|tex| tex.texture()),
+            m.normal_texture().map(|tex| tex.texture()),
+            m.occlusion_texture().map(|tex| tex.texture()),
+        ]
+        .map(|texture| (texture, linear));
+        colors
+            .into_iter()
+            .chain(data)
+            .filter_map(|(texture, format)| Some((texture?.index(), format)))
+            .for_each(|(index, format)| {
+                if !formats[index].contains(&format) {
+                    formats[index].push(format);
+                }
+            });
+    });
     let textures = doc
         .textures()
         .map(|t| {
-            let image = images.get(t.source().index()).unwrap();
-            let image_buffer = match image.format {
-                gltf::image::Format::R8G8B8 => DynamicImage::ImageRgb8(
-                    ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(
-                        image.width,
-                        image.height,
-                        image.pixels.clone(),
-                    )
-                    .unwrap(),
-                )
-                .to_rgba8(),
-                gltf::image::Format::R8G8B8A8 => DynamicImage::ImageRgba8(
-                    ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(
-                        image.width,
-                        image.height,
-                        image.pixels.clone(),
-                    )
-                    .unwrap(),
-                )
-                .to_rgba8(),
-                _ => todo!(),
-            };
-
-            let mag_filter = t
-                .sampler()
-                .mag_filter()
-                .map_or(Default::default(), |filter| match filter {
-                    gltf::texture::MagFilter::Nearest => wgpu::FilterMode::Nearest,
-                    gltf::texture::MagFilter::Linear => wgpu::FilterMode::Linear,
-                });
-            let (min_filter, mipmap_filter) = t.sampler().min_filter().map_or(
-                (Default::default(), Default::default()),
-                |filter| match filter {
-                    gltf::texture::MinFilter::Nearest => {
-                        (wgpu::FilterMode::Nearest, Default::default())
-                    }
-                    gltf::texture::MinFilter::Linear => {
-                        (wgpu::FilterMode::Linear, Default::default())
-                    }
-                    gltf::texture::MinFilter::NearestMipmapNearest => {
-                        (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
-                    }
-                    gltf::texture::MinFilter::LinearMipmapNearest => {
-                        (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
-                    }
-                    gltf::texture::MinFilter::NearestMipmapLinear => {
-                        (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+            let location = format!("texture {}", label(t.name(), t.index()));
+            if formats[t.index()].is_empty() {
+                return Vec::new();
+            }
+            let builder = sampler(t.sampler())
+                .with_anisotropy_clamp(registry.anisotropy_clamp)
+                .with_mipmap_generator(registry.mipmap_generator.clone());
+            let builder = builder
+                .clone()
+                .with_sampler(registry.sampler(device, &builder));
+            if let Some(image) = basisu_sources[t.index()].and_then(|index| doc.images().nth(index))
+            {
+                let ktx2 = image_bytes(&image, path.as_ref().parent(), &buffers)
+                    .and_then(|bytes| Ok(Ktx2::parse(bytes.as_slice(), device.features())?));
+                match ktx2 {
+                    Ok(ktx2) if device.features().contains(ktx2.format.required_features()) => {
+                        return upload_color_spaces(
+                            registry,
+                            &formats[t.index()],
+                            &builder,
+                            |format, builder| {
+                                // The color space of the texels follows the material role too.
+                                let ktx2_format = if format.is_srgb() {
+                                    ktx2.format.add_srgb_suffix()
+                                } else {
+                                    ktx2.format.remove_srgb_suffix()
+                                };
+                                builder.build_levels(
+                                    device,
+                                    queue,
+                                    ktx2_format,
+                                    ktx2.size,
+                                    ktx2.levels.as_slice(),
+                                )
+                            },
+                        );
                     }
-                    gltf::texture::MinFilter::LinearMipmapLinear => {
-                        (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+                    Ok(ktx2) => log::warn!(
+                        "{location} uses {:?} which the device doesn't support, falling back to \
+                         its source image",
+                        ktx2.format
+                    ),
+                    Err(error) => {
+                        log::warn!("{location} {error}, falling back to its source image");
                     }
-                },
-            );
-            let address_mode_u = match t.sampler().wrap_s() {
-                gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
-                gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
-                gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+                }
+            }
+            let Some(image) = &images[t.source().index()] else {
+                log::warn!("{location} has no image, falling back to the material default");
+                return Vec::new();
             };
-            let address_mode_v = match t.sampler().wrap_t() {
-                gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
-                gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
-                gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+            let Some(image) = dynamic_image(image) else {
+                log::warn!("{location} has truncated pixels, falling back to the material default");
+                return Vec::new();
             };
 
-            Arc::new(
-                TextureBuilder::default()
-                    .with_address_mode_u(address_mode_u)
-                    .with_address_mode_v(address_mode_v)
-                    .with_mag_filter(mag_filter)
-                    .with_min_filter(min_filter)
-                    .with_mipmap_filter(mipmap_filter)
-                    .build(device, queue, image_buffer),
+            upload_color_spaces(
+                registry,
+                &formats[t.index()],
+                &builder,
+                |format, builder| {
+                    builder
+                        .with_format(format)
+                        .build(device, queue, image.clone())
+                },
             )
         })
-        .collect::<Vec<_>>();
+        .collect::<Vec<Vec<_>>>();
+    let texture = |texture: gltf::Texture<'_>, format| {
+        textures[texture.index()]
+            .iter()
+            .find(|(texture_format, _)| *texture_format == format)
+            .map(|(_, texture)| texture.clone())
+    };
 
     let materials = doc
         .materials()
         .map(|m| {
+            let json = &root["materials"][m.index().unwrap()];
             let mut builder = MaterialBuilder::default();
+            builder.with_emissive_factor(m.emissive_factor());
             if let Some(tex) = m.emissive_texture() {
-                builder.with_emissive(
-                    m.emissive_factor(),
-                    tex.tex_coord(),
-                    textures[tex.texture().index()].clone(),
-                );
+                if let Some(texture) = texture(tex.texture(), srgb) {
+                    let (transform, tex_coord) = texture_transform(&tex);
+                    builder.with_emissive(m.emissive_factor(), tex_coord, texture);
+                    builder.with_emissive_transform(transform);
+                }
             }
             if let Some(tex) = m.normal_texture() {
-                builder.with_normal(
-                    tex.scale(),
-                    tex.tex_coord(),
-                    textures[tex.texture().index()].clone(),
-                );
+                if let Some(texture) = texture(tex.texture(), linear) {
+                    let (transform, tex_coord) =
+                        json_texture_transform(&json["normalTexture"], tex.tex_coord());
+                    builder.with_normal(tex.scale(), tex_coord, texture);
+                    builder.with_normal_transform(transform);
+                }
             }
             if let Some(tex) = m.occlusion_texture() {
-                builder.with_occlusion(
-                    tex.strength(),
-                    tex.tex_coord(),
-                    textures[tex.texture().index()].clone(),
-                );
+                if let Some(texture) = texture(tex.texture(), linear) {
+                    let (transform, tex_coord) =
+                        json_texture_transform(&json["occlusionTexture"], tex.tex_coord());
+                    builder.with_occlusion(tex.strength(), tex_coord, texture);
+                    builder.with_occlusion_transform(transform);
+                }
             }
             let pbr_metallic_roughness = m.pbr_metallic_roughness();
+            builder.with_base_color_factor(pbr_metallic_roughness.base_color_factor());
+            builder.with_metallic_roughness_factors(
+                pbr_metallic_roughness.metallic_factor(),
+                pbr_metallic_roughness.roughness_factor(),
+            );
             if let Some(tex) = pbr_metallic_roughness.base_color_texture() {
-                builder.with_base_color(
-                    pbr_metallic_roughness.base_color_factor(),
-                    tex.tex_coord(),
-                    textures[tex.texture().index()].clone(),
-                );
+                if let Some(texture) = texture(tex.texture(), srgb) {
+                    let (transform, tex_coord) = texture_transform(&tex);
+                    builder.with_base_color(
+                        pbr_metallic_roughness.base_color_factor(),
+                        tex_coord,
+                        texture,
+                    );
+                    builder.with_base_color_transform(transform);
+                }
             }
             if let Some(tex) = pbr_metallic_roughness.metallic_roughness_texture() {
-                builder.with_metallic_roughness(
-                    pbr_metallic_roughness.metallic_factor(),
-                    pbr_metallic_roughness.roughness_factor(),
-                    tex.tex_coord(),
-                    textures[tex.texture().index()].clone(),
-                );
+                if let Some(texture) = texture(tex.texture(), linear) {
+                    let (transform, tex_coord) = texture_transform(&tex);
+                    builder.with_metallic_roughness(
+                        pbr_metallic_roughness.metallic_factor(),
+                        pbr_metallic_roughness.roughness_factor(),
+                        tex_coord,
+                        texture,
+                    );
+                    builder.with_metallic_roughness_transform(transform);
+                }
             }
 
-            Arc::new(builder.build(device, queue))
+            Arc::new(builder.build(device, queue, registry))
         })
         .collect::<Vec<_>>();
+    // Primitives without a material all share one with the default factors.
+    let default_material = std::cell::OnceCell::new();
 
     let meshes = doc
         .meshes()
@@ -143,113 +287,785 @@ where
             let primitives = m
                 .primitives()
                 .map(|p| {
-                    let reader = p.reader(|buffer| Some(&buffers[buffer.index()]));
-                    let mut vertices = reader
-                        .read_positions()
-                        .unwrap()
-                        .map(|position| VertexAttribute {
-                            position,
-                            ..VertexAttribute::default()
-                        })
-                        .collect::<Vec<_>>();
-                    if let Some(normals) = reader.read_normals() {
-                        normals
-                            .enumerate()
-                            .for_each(|(i, normal)| vertices[i].normal = normal);
-                    }
-                    if let Some(colors) = reader.read_colors(0) {
-                        colors
-                            .into_rgba_f32()
-                            .enumerate()
-                            .for_each(|(i, color_0)| vertices[i].color_0 = color_0);
-                    }
-                    if let Some(tex_coords) = reader.read_tex_coords(0) {
-                        tex_coords
-                            .into_f32()
-                            .enumerate()
-                            .for_each(|(i, tex_coord_0)| vertices[i].tex_coord_0 = tex_coord_0);
-                    }
-                    let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
-                        device,
-                        &wgpu::util::BufferInitDescriptor {
-                            label: Some(
-                                format!("{} vertex buffer", m.name().unwrap_or("unnamed")).as_str(),
-                            ),
-                            contents: bytemuck::cast_slice(vertices.as_slice()),
-                            usage: wgpu::BufferUsages::VERTEX,
-                        },
-                    );
-
-                    let indices = reader
-                        .read_indices()
-                        .unwrap()
-                        .into_u32()
-                        .collect::<Vec<_>>();
-                    let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
-                        device,
-                        &wgpu::util::BufferInitDescriptor {
-                            label: Some(
-                                format!("{} index buffer", m.name().unwrap_or("unnamed")).as_str(),
-                            ),
-                            contents: bytemuck::cast_slice(indices.as_slice()),
-                            usage: wgpu::BufferUsages::INDEX,
-                        },
-                    );
                     let material = p.material().index().map_or_else(
-                        || Arc::new(MaterialBuilder::default().build(device, queue)),
+                        || {
+                            default_material
+                                .get_or_init(|| {
+                                    Arc::new(
+                                        MaterialBuilder::default().build(device, queue, registry),
+                                    )
+                                })
+                                .clone()
+                        },
                         |index| materials[index].clone(),
                     );
-
-                    Arc::new(Primitive {
-                        vertex_buffer,
-                        index_buffer,
-                        index_count: indices.len() as u32,
-                        material,
-                    })
+                    primitive(device, registry, &m, &p, &buffers, material)
                 })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Arc::new(Mesh { primitives }))
+        })
+        .collect::<Result<Vec<_>, ImportError>>()?;
+
+    let skins = doc
+        .skins()
+        .map(|skin| {
+            let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            let joints = skin
+                .joints()
+                .map(|joint| NodeIndex::new(joint.index()))
                 .collect::<Vec<_>>();
+            let inverse_bind_matrices = reader.read_inverse_bind_matrices().map_or_else(
+                || vec![Matrix4::identity(); joints.len()],
+                |matrices| matrices.map(Matrix4::from).collect(),
+            );
+            if inverse_bind_matrices.len() != joints.len() {
+                return Err(ImportError::MalformedAccessor {
+                    location: format!("skin {}", label(skin.name(), skin.index())),
+                    accessor: "inverse bind matrices",
+                });
+            }
 
-            Arc::new(Mesh { primitives })
+            Ok(Arc::new(Skin {
+                joints,
+                inverse_bind_matrices,
+            }))
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, ImportError>>()?;
 
     let mut stable_graph = Scene::new();
     doc.nodes().for_each(|node| {
-        let transform_matrix = Matrix4::from(node.transform().matrix());
+        let (translation, rotation, scale) = node.transform().decomposed();
         let mesh = node.mesh().map(|mesh| meshes[mesh.index()].clone());
+        let skin = node.skin().map(|skin| skins[skin.index()].clone());
+        let light = node.light().map(light);
+        let camera = node
+            .camera()
+            .map(|camera| Projection::from(camera.projection()));
+        let weights = node
+            .weights()
+            .or_else(|| node.mesh().and_then(|mesh| mesh.weights()))
+            .map_or_else(Vec::new, <[f32]>::to_vec);
 
-        stable_graph.add_node(Node {
-            transform_matrix,
+        let node = Node {
             mesh,
-        });
+            skin,
+            light,
+            camera,
+            weights,
+            ..Default::default()
+        }
+        .with_translation(Vector3::from(translation))
+        .with_rotation(UnitQuaternion::from_quaternion(Quaternion::from(rotation)))
+        .with_scale(Vector3::from(scale));
+        let rest_pose = Pose::from(&node);
+        stable_graph.add_node(node.with_rest_pose(rest_pose));
     });
     let edges = doc
-        .default_scene()
-        .unwrap()
         .nodes()
-        .flat_map(inorder_traversal_edges)
+        .flat_map(|node| {
+            node.children()
+                .map(move |child| (node.index() as u32, child.index() as u32))
+        })
         .collect::<Vec<_>>();
     stable_graph.extend_with_edges(edges.as_slice());
 
-    stable_graph
+    let scene = match selection {
+        SceneSelection::Default => doc.default_scene().or_else(|| doc.scenes().next()),
+        SceneSelection::Index(index) => Some(
+            doc.scenes()
+                .nth(index)
+                .ok_or_else(|| ImportError::MissingScene(index.to_string()))?,
+        ),
+        SceneSelection::Name(name) => Some(
+            doc.scenes()
+                .find(|scene| scene.name() == Some(name))
+                .ok_or_else(|| ImportError::MissingScene(format!("named {name}")))?,
+        ),
+    };
+    if let Some(scene) = scene {
+        let mut dfs = Dfs::empty(&stable_graph);
+        dfs.stack
+            .extend(scene.nodes().map(|node| NodeIndex::new(node.index())));
+        while dfs.next(&stable_graph).is_some() {}
+        stable_graph.retain_nodes(|_, index| dfs.discovered.contains(index.index()));
+    }
+    update_world_matrices(&mut stable_graph);
+
+    Ok(Imported {
+        scene: stable_graph,
+        animations: animation::import(&doc, &buffers)?,
+    })
+}
+
+/// Reads the document and buffers of a glTF file, images are left to the caller. The JSON is
+/// returned too, for the extensions gltf doesn't parse or only parses in some places.
+fn open(
+    path: &Path,
+) -> Result<(gltf::Document, Vec<gltf::buffer::Data>, serde_json::Value), ImportError> {
+    let bytes = std::fs::read(path)?;
+    let (json, blob) = if bytes.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(bytes.as_slice())?;
+        (
+            glb.json.into_owned(),
+            glb.bin.map(std::borrow::Cow::into_owned),
+        )
+    } else {
+        (bytes, None)
+    };
+    let mut json = serde_json::from_slice::<serde_json::Value>(json.as_slice())
+        .map_err(|error| ImportError::Parse(gltf::Error::Deserialize(error)))?;
+    // `KHR_texture_basisu` lets textures leave out their source when the extension is required,
+    // which gltf doesn't accept, the KTX2 image stands in for it.
+    if let Some(textures) = json
+        .get_mut("textures")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        for texture in textures {
+            let source = texture["extensions"]["KHR_texture_basisu"]["source"].clone();
+            if texture.get("source").is_none() && source.is_u64() {
+                texture["source"] = source;
+            }
+        }
+    }
+    let document = serde_json::from_value(json.clone())
+        .map_err(|error| ImportError::Parse(gltf::Error::Deserialize(error)))
+        .and_then(|root| Ok(gltf::Document::from_json(root)?))?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+    Ok((document, buffers, json))
+}
+
+/// Images of the textures using `KHR_texture_basisu`, one entry per texture.
+fn basisu_sources(root: &serde_json::Value) -> Vec<Option<usize>> {
+    let textures = root["textures"].as_array().map_or(&[][..], Vec::as_slice);
+    textures
+        .iter()
+        .map(|texture| {
+            let source = texture["extensions"]["KHR_texture_basisu"]["source"].as_u64()?;
+            usize::try_from(source).ok()
+        })
+        .collect()
+}
+
+/// `KHR_texture_transform` of a texture info and the texture coordinates it uses, which the
+/// extension can override.
+fn texture_transform(info: &gltf::texture::Info<'_>) -> (TextureTransform, u32) {
+    let transform = info.texture_transform();
+    let tex_coord = transform
+        .as_ref()
+        .and_then(gltf::texture::TextureTransform::tex_coord)
+        .unwrap_or_else(|| info.tex_coord());
+    (
+        transform.map_or_else(TextureTransform::default, TextureTransform::from),
+        imported_tex_coord(tex_coord),
+    )
+}
+
+/// Same as `texture_transform` for the normal and occlusion textures, whose extensions gltf only
+/// keeps in their JSON.
+fn json_texture_transform(info: &serde_json::Value, tex_coord: u32) -> (TextureTransform, u32) {
+    let transform = serde_json::from_value::<gltf::json::extensions::texture::TextureTransform>(
+        info["extensions"]["KHR_texture_transform"].clone(),
+    );
+    match transform {
+        Ok(transform) => {
+            let tex_coord = transform.tex_coord.unwrap_or(tex_coord);
+            (
+                TextureTransform::from(transform),
+                imported_tex_coord(tex_coord),
+            )
+        }
+        Err(_) => (TextureTransform::default(), imported_tex_coord(tex_coord)),
+    }
+}
+
+/// Only the first two sets of texture coordinates are imported.
+fn imported_tex_coord(tex_coord: u32) -> u32 {
+    if tex_coord > 1 {
+        log::warn!("TEXCOORD_{tex_coord} isn't imported, sampling TEXCOORD_0 instead");
+        return 0;
+    }
+    tex_coord
+}
+
+/// Undecoded bytes of an image, for the containers gltf can't decode.
+fn image_bytes(
+    image: &gltf::Image<'_>,
+    base: Option<&Path>,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<u8>, ImportError> {
+    match image.source() {
+        gltf::image::Source::View { view, .. } => {
+            let buffer = &buffers[view.buffer().index()];
+            Ok(buffer[view.offset()..view.offset() + view.length()].to_vec())
+        }
+        gltf::image::Source::Uri { uri, .. } => match uri.strip_prefix("data:") {
+            Some(data) => data
+                .split_once(";base64,")
+                .and_then(|(_, data)| base64::decode(data).ok())
+                .ok_or_else(|| ImportError::Unsupported {
+                    location: format!("image {}", image.index()),
+                    feature: "data URI encoding".to_string(),
+                }),
+            None => Ok(std::fs::read(
+                base.unwrap_or_else(|| Path::new("")).join(uri),
+            )?),
+        },
+    }
+}
+
+/// Copies the values of an accessor into the matching vertices, `None` when it doesn't have one
+/// value per vertex.
+fn set_attribute<V, T>(
+    vertices: &mut [V],
+    values: impl Iterator<Item = T>,
+    set: impl Fn(&mut V, T),
+) -> Option<()> {
+    let values = values.collect::<Vec<_>>();
+    if values.len() != vertices.len() {
+        return None;
+    }
+    vertices
+        .iter_mut()
+        .zip(values)
+        .for_each(|(vertex, value)| set(vertex, value));
+    Some(())
+}
+
+/// Names a glTF item for error messages, by name when it has one and by index otherwise.
+pub(crate) fn label(name: Option<&str>, index: usize) -> String {
+    name.map_or_else(|| format!("#{index}"), |name| format!("\"{name}\""))
+}
+
+/// Texture builder with the filters and address modes of a glTF sampler, mipmaps are generated
+/// when its minification filter uses them.
+fn sampler(sampler: gltf::texture::Sampler<'_>) -> TextureBuilder {
+    let mag_filter = sampler
+        .mag_filter()
+        .map_or(Default::default(), |filter| match filter {
+            gltf::texture::MagFilter::Nearest => wgpu::FilterMode::Nearest,
+            gltf::texture::MagFilter::Linear => wgpu::FilterMode::Linear,
+        });
+    let (min_filter, mipmap_filter) = sampler.min_filter().map_or(
+        (Default::default(), Default::default()),
+        |filter| match filter {
+            gltf::texture::MinFilter::Nearest => (wgpu::FilterMode::Nearest, Default::default()),
+            gltf::texture::MinFilter::Linear => (wgpu::FilterMode::Linear, Default::default()),
+            gltf::texture::MinFilter::NearestMipmapNearest => {
+                (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+            }
+            gltf::texture::MinFilter::LinearMipmapNearest => {
+                (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+            }
+            gltf::texture::MinFilter::NearestMipmapLinear => {
+                (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+            }
+            gltf::texture::MinFilter::LinearMipmapLinear => {
+                (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+            }
+        },
+    );
+    let address_mode_u = match sampler.wrap_s() {
+        gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let address_mode_v = match sampler.wrap_t() {
+        gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+
+    let mipmaps = !matches!(
+        sampler.min_filter(),
+        None | Some(gltf::texture::MinFilter::Nearest | gltf::texture::MinFilter::Linear)
+    );
+    TextureBuilder::default()
+        .with_address_mode_u(address_mode_u)
+        .with_address_mode_v(address_mode_v)
+        .with_mag_filter(mag_filter)
+        .with_min_filter(min_filter)
+        .with_mipmap_filter(mipmap_filter)
+        .with_mipmaps(mipmaps)
+}
+
+/// Rebuilds the image decoded by gltf from its raw pixels, `None` when they are truncated.
+fn dynamic_image(data: &gltf::image::Data) -> Option<DynamicImage> {
+    let (width, height) = (data.width, data.height);
+    let bytes = data.pixels.clone();
+    let words = || {
+        data.pixels
+            .chunks_exact(2)
+            .map(|word| u16::from_ne_bytes([word[0], word[1]]))
+            .collect::<Vec<_>>()
+    };
+    let floats = || {
+        data.pixels
+            .chunks_exact(4)
+            .map(|float| f32::from_ne_bytes([float[0], float[1], float[2], float[3]]))
+            .collect::<Vec<_>>()
+    };
+
+    match data.format {
+        gltf::image::Format::R8 => {
+            ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLuma8)
+        }
+        gltf::image::Format::R8G8 => {
+            ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageLumaA8)
+        }
+        gltf::image::Format::R8G8B8 => {
+            ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgb8)
+        }
+        gltf::image::Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, bytes).map(DynamicImage::ImageRgba8)
+        }
+        gltf::image::Format::R16 => {
+            ImageBuffer::from_raw(width, height, words()).map(DynamicImage::ImageLuma16)
+        }
+        gltf::image::Format::R16G16 => {
+            ImageBuffer::from_raw(width, height, words()).map(DynamicImage::ImageLumaA16)
+        }
+        gltf::image::Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, words()).map(DynamicImage::ImageRgb16)
+        }
+        gltf::image::Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, words()).map(DynamicImage::ImageRgba16)
+        }
+        gltf::image::Format::R32G32B32FLOAT => {
+            ImageBuffer::from_raw(width, height, floats()).map(DynamicImage::ImageRgb32F)
+        }
+        gltf::image::Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, floats()).map(DynamicImage::ImageRgba32F)
+        }
+    }
 }
 
-fn inorder_traversal_edges(node: gltf::Node<'_>) -> Vec<(u32, u32)> {
-    node.children()
-        .flat_map(|child| {
-            std::iter::once((node.index() as u32, child.index() as u32))
-                .chain(inorder_traversal_edges(child))
+fn primitive(
+    device: &wgpu::Device,
+    registry: &MaterialRegistry,
+    mesh: &gltf::Mesh<'_>,
+    primitive: &gltf::Primitive<'_>,
+    buffers: &[gltf::buffer::Data],
+    material: Arc<Material>,
+) -> Result<Arc<Primitive>, ImportError> {
+    let location = format!(
+        "mesh {} primitive {}",
+        label(mesh.name(), mesh.index()),
+        primitive.index()
+    );
+    let malformed = |accessor| ImportError::MalformedAccessor {
+        location: location.clone(),
+        accessor,
+    };
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let mut vertices = reader
+        .read_positions()
+        .ok_or_else(|| malformed("POSITION"))?
+        .map(|position| VertexAttribute {
+            position,
+            ..VertexAttribute::default()
         })
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+    if let Some(normals) = reader.read_normals() {
+        set_attribute(&mut vertices, normals, |vertex, normal| {
+            vertex.normal = normal
+        })
+        .ok_or_else(|| malformed("NORMAL"))?;
+    }
+    if let Some(tangents) = reader.read_tangents() {
+        set_attribute(&mut vertices, tangents, |vertex, tangent| {
+            vertex.tangent = tangent;
+        })
+        .ok_or_else(|| malformed("TANGENT"))?;
+    }
+    if let Some(colors) = reader.read_colors(0) {
+        set_attribute(&mut vertices, colors.into_rgba_f32(), |vertex, color_0| {
+            vertex.color_0 = color_0;
+        })
+        .ok_or_else(|| malformed("COLOR_0"))?;
+    }
+    // Only the first set tints the material, the others are left to the application.
+    let colors = (1..)
+        .map_while(|set| reader.read_colors(set))
+        .map(|colors| {
+            let colors = colors.into_rgba_f32().collect::<Vec<_>>();
+            (colors.len() == vertices.len())
+                .then_some(colors)
+                .ok_or_else(|| malformed("COLOR_n"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(tex_coords) = reader.read_tex_coords(0) {
+        set_attribute(
+            &mut vertices,
+            tex_coords.into_f32(),
+            |vertex, tex_coord_0| {
+                vertex.tex_coord_0 = tex_coord_0;
+            },
+        )
+        .ok_or_else(|| malformed("TEXCOORD_0"))?;
+    }
+    if let Some(tex_coords) = reader.read_tex_coords(1) {
+        set_attribute(
+            &mut vertices,
+            tex_coords.into_f32(),
+            |vertex, tex_coord_1| {
+                vertex.tex_coord_1 = tex_coord_1;
+            },
+        )
+        .ok_or_else(|| malformed("TEXCOORD_1"))?;
+    }
+    if let Some(joints) = reader.read_joints(0) {
+        set_attribute(&mut vertices, joints.into_u16(), |vertex, joints_0| {
+            vertex.joints_0 = joints_0.map(u32::from);
+        })
+        .ok_or_else(|| malformed("JOINTS_0"))?;
+    }
+    if let Some(weights) = reader.read_weights(0) {
+        set_attribute(&mut vertices, weights.into_f32(), |vertex, weights_0| {
+            vertex.weights_0 = weights_0;
+        })
+        .ok_or_else(|| malformed("WEIGHTS_0"))?;
+    }
+    let indices = reader
+        .read_indices()
+        .map(|indices| indices.into_u32().collect::<Vec<_>>());
+    let element_count = indices.as_ref().map_or(vertices.len(), Vec::len);
+    let primitive_size = match primitive.mode() {
+        gltf::mesh::Mode::Lines => 2,
+        gltf::mesh::Mode::Triangles => 3,
+        _ => 1,
+    };
+    if !element_count.is_multiple_of(primitive_size)
+        || indices
+            .iter()
+            .flatten()
+            .any(|&index| index as usize >= vertices.len())
+    {
+        return Err(malformed("indices"));
+    }
+    let sequential = || (0..vertices.len() as u32).collect::<Vec<_>>();
+    let (topology, indices) = topology(primitive.mode(), indices, vertices.len());
+    if topology == wgpu::PrimitiveTopology::TriangleList
+        && reader.read_tangents().is_none()
+        && reader.read_normals().is_some()
+        && reader.read_tex_coords(material.normal_tex_coord).is_some()
+    {
+        let indices = indices.clone().unwrap_or_else(sequential);
+        bevy_mikktspace::generate_tangents(&mut TangentSpace {
+            vertices: vertices.as_mut_slice(),
+            indices: indices.as_slice(),
+            tex_coord: material.normal_tex_coord,
+        });
+    }
+
+    let mut deltas = Vec::new();
+    let mut target_count = 0;
+    for (positions, normals, _) in reader.read_morph_targets() {
+        let mut target = vec![MorphDelta::default(); vertices.len()];
+        if let Some(positions) = positions {
+            set_attribute(&mut target, positions, |delta, [x, y, z]| {
+                delta.position = [x, y, z, 0.0];
+            })
+            .ok_or_else(|| malformed("morph target POSITION"))?;
+        }
+        if let Some(normals) = normals {
+            set_attribute(&mut target, normals, |delta, [x, y, z]| {
+                delta.normal = [x, y, z, 0.0];
+            })
+            .ok_or_else(|| malformed("morph target NORMAL"))?;
+        }
+        deltas.extend(target);
+        target_count += 1;
+    }
+    let morph_targets = MorphTargets::new(
+        device,
+        &registry.morph_targets_bind_group_layout,
+        deltas.as_slice(),
+        vertices.len() as u32,
+        target_count,
+    );
+
+    let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some(format!("{} vertex buffer", mesh.name().unwrap_or("unnamed")).as_str()),
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+            usage: wgpu::BufferUsages::VERTEX,
+        },
+    );
+    let index_buffer = indices.as_ref().map(|indices| {
+        wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(format!("{} index buffer", mesh.name().unwrap_or("unnamed")).as_str()),
+                contents: bytemuck::cast_slice(indices.as_slice()),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        )
+    });
+    Ok(Arc::new(Primitive {
+        topology,
+        vertex_buffer,
+        vertex_count: vertices.len() as u32,
+        index_buffer,
+        index_count: indices.map_or(0, |indices| indices.len() as u32),
+        colors,
+        material,
+        morph_targets,
+    }))
+}
+
+/// wgpu topology drawing a glTF primitive mode and the indices to draw it with. Fans and loops
+/// have no wgpu topology, they are drawn as lists and strips instead.
+fn topology(
+    mode: gltf::mesh::Mode,
+    indices: Option<Vec<u32>>,
+    vertex_count: usize,
+) -> (wgpu::PrimitiveTopology, Option<Vec<u32>>) {
+    let sequential = || (0..vertex_count as u32).collect::<Vec<_>>();
+    match mode {
+        gltf::mesh::Mode::Points => (wgpu::PrimitiveTopology::PointList, indices),
+        gltf::mesh::Mode::Lines => (wgpu::PrimitiveTopology::LineList, indices),
+        gltf::mesh::Mode::LineLoop => {
+            let mut strip = indices.unwrap_or_else(sequential);
+            if let Some(&first) = strip.first() {
+                strip.push(first);
+            }
+            (wgpu::PrimitiveTopology::LineStrip, Some(strip))
+        }
+        gltf::mesh::Mode::LineStrip => (wgpu::PrimitiveTopology::LineStrip, indices),
+        gltf::mesh::Mode::Triangles => (wgpu::PrimitiveTopology::TriangleList, indices),
+        gltf::mesh::Mode::TriangleStrip => (wgpu::PrimitiveTopology::TriangleStrip, indices),
+        gltf::mesh::Mode::TriangleFan => {
+            let fan = indices.unwrap_or_else(sequential);
+            let list = (2..fan.len())
+                .flat_map(|index| [fan[0], fan[index - 1], fan[index]])
+                .collect();
+            (wgpu::PrimitiveTopology::TriangleList, Some(list))
+        }
+    }
+}
+
+/// A mesh to draw with its model matrix, skinned meshes also carry the joint matrices that
+/// take their vertices to world space in place of the model matrix.
+pub struct MeshInstance {
+    pub model_matrix: Matrix4<f32>,
+    pub mesh: Arc<Mesh>,
+    pub joint_matrices: Vec<Matrix4<f32>>,
+    /// Morph target weights, one per target of the mesh's primitives.
+    pub weights: Vec<f32>,
+}
+
+/// Overrides the morph target weights of every mesh in the `Scene` of its entity.
+#[derive(Debug, Clone, Default)]
+pub struct MorphWeights(pub Vec<f32>);
+
+/// Mesh instances of every `Scene` in the world placed by its entity's transform.
+pub fn meshes(world: &SubWorld) -> Vec<MeshInstance> {
+    <(&Scene, &Matrix4<f32>, Option<&MorphWeights>)>::query()
+        .iter(world)
+        .flat_map(|(scene, transform_matrix, morph_weights)| {
+            let mut instances = mesh_instances(scene, transform_matrix);
+            if let Some(MorphWeights(weights)) = morph_weights {
+                instances.iter_mut().for_each(|instance| {
+                    instance.weights = weights.clone();
+                    instance.weights.resize(instance.mesh.target_count(), 0.0);
+                });
+            }
+            instances
+        })
+        .collect()
+}
+
+/// Walks the scene graph from its root and returns every mesh along with its model matrix.
+pub fn mesh_instances(scene: &Scene, transform_matrix: &Matrix4<f32>) -> Vec<MeshInstance> {
+    scene
+        .node_indices()
+        .filter_map(|index| {
+            let node = &scene[index];
+            let joint_matrices = node.skin.as_ref().map_or_else(Vec::new, |skin| {
+                skin.joints
+                    .iter()
+                    .zip(skin.inverse_bind_matrices.iter())
+                    .map(|(&joint, inverse_bind_matrix)| {
+                        // Joints outside the imported scene are taken at the scene origin.
+                        let joint_matrix = scene
+                            .node_weight(joint)
+                            .map_or_else(Matrix4::identity, |joint| *joint.world_matrix());
+                        transform_matrix * joint_matrix * inverse_bind_matrix
+                    })
+                    .collect()
+            });
+
+            let mesh = node.mesh.clone()?;
+            let mut weights = node.weights.clone();
+            weights.resize(mesh.target_count(), 0.0);
+
+            Some(MeshInstance {
+                model_matrix: transform_matrix * node.world_matrix(),
+                mesh,
+                joint_matrices,
+                weights,
+            })
+        })
+        .collect()
+}
+
+/// Returns every light of the scene along with its world transform.
+pub fn light_instances(
+    scene: &Scene,
+    transform_matrix: &Matrix4<f32>,
+) -> Vec<(Matrix4<f32>, Light)> {
+    scene
+        .node_weights()
+        .filter_map(|node| Some((transform_matrix * node.world_matrix(), node.light?)))
+        .collect()
+}
+
+/// Returns every camera of the scene along with its world transform.
+pub fn camera_instances(
+    scene: &Scene,
+    transform_matrix: &Matrix4<f32>,
+) -> Vec<(Matrix4<f32>, Projection)> {
+    scene
+        .node_weights()
+        .filter_map(|node| Some((transform_matrix * node.world_matrix(), node.camera?)))
+        .collect()
+}
+
+/// Nodes without a parent, a scene may have several.
+pub fn roots(scene: &Scene) -> impl Iterator<Item = NodeIndex> + '_ {
+    scene.externals(petgraph::Direction::Incoming)
+}
+
+/// Recomputes the cached world matrices of the nodes whose transform changed and of all their
+/// descendants, parents before children.
+pub fn update_world_matrices(scene: &mut Scene) {
+    let mut stack = roots(scene)
+        .map(|root| (root, Matrix4::identity(), false))
+        .collect::<Vec<_>>();
+    while let Some((index, parent_matrix, parent_changed)) = stack.pop() {
+        let node = &mut scene[index];
+        let changed = parent_changed || node.dirty;
+        if changed {
+            node.world_matrix = parent_matrix * node.local_matrix();
+            node.dirty = false;
+        }
+        let world_matrix = node.world_matrix;
+        stack.extend(
+            scene
+                .neighbors(index)
+                .map(|child| (child, world_matrix, changed)),
+        );
+    }
+}
+
+#[system(for_each)]
+pub fn update_transforms(scene: &mut Scene) {
+    update_world_matrices(scene);
+}
+
+fn light(light: gltf::khr_lights_punctual::Light<'_>) -> Light {
+    let [r, g, b] = light.color();
+    let color = [r, g, b, 1.0];
+    match light.kind() {
+        gltf::khr_lights_punctual::Kind::Directional => Light::Directional(DirectionalLight {
+            color,
+            intensity: light.intensity(),
+        }),
+        gltf::khr_lights_punctual::Kind::Point => Light::Point(PointLight {
+            color,
+            intensity: light.intensity(),
+            range: light.range(),
+            ..Default::default()
+        }),
+        gltf::khr_lights_punctual::Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => Light::Spot(SpotLight {
+            color,
+            intensity: light.intensity(),
+            range: light.range(),
+            inner_cone_angle,
+            outer_cone_angle,
+        }),
+    }
+}
+
+/// Triangle list view over a primitive used to generate its `MikkTSpace` tangents, in the
+/// texture coordinates set `tex_coord` of its normal texture.
+struct TangentSpace<'a> {
+    vertices: &'a mut [VertexAttribute],
+    indices: &'a [u32],
+    tex_coord: u32,
+}
+
+impl TangentSpace<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &VertexAttribute {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentSpace<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let vertex = self.vertex(face, vert);
+        if self.tex_coord == 1 {
+            vertex.tex_coord_1
+        } else {
+            vertex.tex_coord_0
+        }
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.vertices[self.indices[face * 3 + vert] as usize].tangent = tangent;
+    }
 }
 
 pub struct Texture {
-    pub texture: wgpu::Texture,
+    pub texture: Arc<wgpu::Texture>,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    pub sampler: Arc<wgpu::Sampler>,
 }
 
-#[derive(Default)]
+impl Texture {
+    /// Another view of the same texels, decoded from sRGB or not. The texture must have been
+    /// built with `TextureBuilder::with_srgb_views`.
+    pub fn view_as(&self, srgb: bool) -> Self {
+        let format = self.texture.format();
+        let view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("RGBA texture view"),
+            format: Some(if srgb {
+                format.add_srgb_suffix()
+            } else {
+                format.remove_srgb_suffix()
+            }),
+            ..wgpu::TextureViewDescriptor::default()
+        });
+        Self {
+            texture: self.texture.clone(),
+            view,
+            sampler: self.sampler.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct TextureBuilder {
     address_mode_u: wgpu::AddressMode,
     address_mode_v: wgpu::AddressMode,
@@ -257,6 +1073,12 @@ pub struct TextureBuilder {
     mag_filter: wgpu::FilterMode,
     min_filter: wgpu::FilterMode,
     mipmap_filter: wgpu::FilterMode,
+    format: Option<wgpu::TextureFormat>,
+    mipmaps: bool,
+    mipmap_generator: Option<Arc<MipmapGenerator>>,
+    srgb_views: bool,
+    anisotropy_clamp: u16,
+    sampler: Option<Arc<wgpu::Sampler>>,
 }
 
 impl TextureBuilder {
@@ -290,116 +1112,768 @@ impl TextureBuilder {
         self
     }
 
-    pub fn build(self, device: &wgpu::Device, queue: &wgpu::Queue, image: RgbaImage) -> Texture {
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Generates the full mip chain of the texture on the GPU.
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Generates mipmaps with the cached pipelines of an existing generator instead of creating
+    /// one for the texture.
+    pub fn with_mipmap_generator(mut self, mipmap_generator: Arc<MipmapGenerator>) -> Self {
+        self.mipmap_generator = Some(mipmap_generator);
+        self
+    }
+
+    /// Allows viewing the texels in the other color space too with `Texture::view_as`, which
+    /// needs `wgpu::DownlevelFlags::VIEW_FORMATS`. Ignored for formats without an sRGB variant.
+    pub fn with_srgb_views(mut self, srgb_views: bool) -> Self {
+        self.srgb_views = srgb_views;
+        self
+    }
+
+    /// Maximum anisotropy of the sampler, only applied when every filter is linear.
+    pub fn with_anisotropy_clamp(mut self, anisotropy_clamp: u16) -> Self {
+        self.anisotropy_clamp = anisotropy_clamp;
+        self
+    }
+
+    /// Samples the texture with an existing sampler instead of creating one, the filters,
+    /// address modes and anisotropy of the builder are then ignored.
+    pub fn with_sampler(mut self, sampler: Arc<wgpu::Sampler>) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    fn sampler_key(&self) -> SamplerKey {
+        let anisotropic = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear);
+        SamplerKey {
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: if anisotropic {
+                self.anisotropy_clamp.max(1)
+            } else {
+                1
+            },
+        }
+    }
+
+    /// Uploads 8-bit images as RGBA8 in the builder format. 16-bit and float images keep their
+    /// precision in `Rgba16Float`, decoded to linear on upload when the builder format is sRGB.
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: impl Into<DynamicImage>,
+    ) -> Texture {
+        let image = image.into();
         let size = wgpu::Extent3d {
             width: image.width(),
             height: image.height(),
             depth_or_array_layers: 1,
         };
+        let format = self.format.unwrap_or(wgpu::TextureFormat::Rgba8UnormSrgb);
+        let (format, texels) = match image {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_) => (format, image.into_rgba8().into_raw()),
+            image => {
+                let texels = image
+                    .into_rgba32f()
+                    .pixels()
+                    .flat_map(|&Rgba([r, g, b, a])| {
+                        let [r, g, b] = if format.is_srgb() {
+                            [r, g, b].map(srgb_to_linear)
+                        } else {
+                            [r, g, b]
+                        };
+                        [r, g, b, a].map(half::f16::from_f32)
+                    })
+                    .collect::<Vec<_>>();
+                (
+                    wgpu::TextureFormat::Rgba16Float,
+                    bytemuck::cast_slice(texels.as_slice()).to_vec(),
+                )
+            }
+        };
+        self.build_levels(device, queue, format, size, &[texels])
+    }
+
+    /// Uploads texels already in `format`, one slice per mip level starting from the base one.
+    /// Mipmaps are only generated for a single uncompressed level.
+    pub fn build_levels(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        levels: &[Vec<u8>],
+    ) -> Texture {
+        let generate = self.mipmaps && levels.len() == 1 && !format.is_compressed();
+        let (mip_level_count, usage) = if generate {
+            (
+                size.max_mips(wgpu::TextureDimension::D2),
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            )
+        } else {
+            (levels.len() as u32, wgpu::TextureUsages::empty())
+        };
+        let other_color_space = if format.is_srgb() {
+            format.remove_srgb_suffix()
+        } else {
+            format.add_srgb_suffix()
+        };
+        let view_formats = if self.srgb_views && other_color_space != format {
+            vec![other_color_space]
+        } else {
+            Vec::new()
+        };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("RGBA8 texture"),
+            label: Some("RGBA texture"),
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | usage,
+            view_formats: view_formats.as_slice(),
         });
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &image.into_raw(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * size.width),
-                rows_per_image: Some(size.height),
-            },
-            size,
-        );
+        let (block_width, block_height) = format.block_dimensions();
+        levels.iter().enumerate().for_each(|(mip_level, texels)| {
+            let size = size
+                .mip_level_size(mip_level as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                texels.as_slice(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(
+                        format.block_size(None).unwrap() * size.width / block_width,
+                    ),
+                    rows_per_image: Some(size.height / block_height),
+                },
+                size,
+            );
+        });
+        if generate {
+            self.mipmap_generator
+                .clone()
+                .unwrap_or_else(|| Arc::new(MipmapGenerator::new(device)))
+                .generate(device, queue, &texture);
+        }
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("RGBA8 texture view"),
+            label: Some("RGBA texture view"),
             ..wgpu::TextureViewDescriptor::default()
         });
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("RGBA8 sampler"),
+        let sampler = self
+            .sampler
+            .clone()
+            .unwrap_or_else(|| Arc::new(self.sampler_key().create(device)));
+
+        Texture {
+            texture: Arc::new(texture),
+            view,
+            sampler,
+        }
+    }
+
+    pub fn from_color(device: &wgpu::Device, queue: &wgpu::Queue, rgba: Rgba<u8>) -> Texture {
+        let mut image_buffer = image::RgbaImage::new(1, 1);
+        image_buffer.put_pixel(0, 0, rgba);
+        Self::default().build(device, queue, image_buffer)
+    }
+
+    /// Single texel texture holding data rather than a color, it is sampled without sRGB
+    /// decoding.
+    pub fn from_linear_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: Rgba<u8>,
+    ) -> Texture {
+        let mut image_buffer = image::RgbaImage::new(1, 1);
+        image_buffer.put_pixel(0, 0, rgba);
+        Self::default()
+            .with_format(wgpu::TextureFormat::Rgba8Unorm)
+            .build(device, queue, image_buffer)
+    }
+}
+
+/// Sampler state a `TextureBuilder` ends up with, samplers are shared between the textures
+/// with the same one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    address_mode_u: wgpu::AddressMode,
+    address_mode_v: wgpu::AddressMode,
+    mag_filter: wgpu::FilterMode,
+    min_filter: wgpu::FilterMode,
+    mipmap_filter: wgpu::FilterMode,
+    anisotropy_clamp: u16,
+}
+
+impl SamplerKey {
+    fn create(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("RGBA sampler"),
             address_mode_u: self.address_mode_u,
             address_mode_v: self.address_mode_v,
             mag_filter: self.mag_filter,
             min_filter: self.min_filter,
             mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy_clamp,
             ..wgpu::SamplerDescriptor::default()
+        })
+    }
+}
+
+/// Fills every mip level of a texture by downsampling the previous one with a linear filter,
+/// with one blit pipeline per texture format created the first time it is needed.
+pub struct MipmapGenerator {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: Mutex<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>>,
+    level_copies: bool,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mipmap"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..wgpu::SamplerDescriptor::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            pipelines: Mutex::new(HashMap::new()),
+            level_copies: false,
+        }
+    }
+
+    /// Copies each level to a texture of its own before downsampling it, for the GL backend
+    /// which samples views ignoring their mip range.
+    pub const fn with_level_copies(mut self, level_copies: bool) -> Self {
+        self.level_copies = level_copies;
+        self
+    }
+
+    fn pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Arc<wgpu::RenderPipeline> {
+        self.pipelines
+            .lock()
+            .unwrap()
+            .entry(format)
+            .or_insert_with(|| {
+                Arc::new(
+                    RenderNodeBuilder::default()
+                        .with_name("mipmap")
+                        .with_color_attachment_format(format)
+                        .with_shader_source(include_str!("mipmap.wgsl").into())
+                        .with_bind_group_layout(&self.bind_group_layout)
+                        .build(device),
+                )
+            })
+            .clone()
+    }
+
+    /// Renders every level from the previous one, all of them in a single submission.
+    pub fn generate(&self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let render_pipeline = self.pipeline(device, texture.format());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("mipmap"),
+        });
+        (1..texture.mip_level_count()).for_each(|mip_level| {
+            let source_view = if self.level_copies {
+                let size = texture
+                    .size()
+                    .mip_level_size(mip_level - 1, wgpu::TextureDimension::D2);
+                let source = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("mipmap source"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: texture.format(),
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                encoder.copy_texture_to_texture(
+                    wgpu::ImageCopyTexture {
+                        texture,
+                        mip_level: mip_level - 1,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    source.as_image_copy(),
+                    size,
+                );
+                source.create_view(&wgpu::TextureViewDescriptor::default())
+            } else {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mipmap source"),
+                    base_mip_level: mip_level - 1,
+                    mip_level_count: Some(1),
+                    ..wgpu::TextureViewDescriptor::default()
+                })
+            };
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("mipmap"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("mipmap"),
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                ..wgpu::TextureViewDescriptor::default()
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&render_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        });
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+/// Uploads a texture for each color space it is sampled in. With `MaterialRegistry::srgb_views`
+/// the first upload is viewed in the other color space instead, unless its format has no sRGB
+/// variant and the texels themselves differ.
+fn upload_color_spaces(
+    registry: &MaterialRegistry,
+    formats: &[wgpu::TextureFormat],
+    builder: &TextureBuilder,
+    upload: impl Fn(wgpu::TextureFormat, TextureBuilder) -> Texture,
+) -> Vec<(wgpu::TextureFormat, Arc<Texture>)> {
+    let srgb_views = registry.srgb_views && formats.len() > 1;
+    let mut textures: Vec<(wgpu::TextureFormat, Arc<Texture>)> = Vec::new();
+    for &format in formats {
+        let texture = match textures.first() {
+            Some((_, first))
+                if srgb_views
+                    && first.texture.format().add_srgb_suffix()
+                        != first.texture.format().remove_srgb_suffix() =>
+            {
+                first.view_as(format.is_srgb())
+            }
+            _ => upload(format, builder.clone().with_srgb_views(srgb_views)),
+        };
+        textures.push((format, Arc::new(texture)));
+    }
+    textures
+}
+
+/// sRGB transfer function decoding, for textures stored in formats without an sRGB variant.
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// GPU resources shared by every material: the bind group layout the geometry pass draws them
+/// with, the textures of unset slots and the samplers. It also holds the layout of the morph
+/// targets of every primitive.
+pub struct MaterialRegistry {
+    pub bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    pub morph_targets_bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    /// White in sRGB, for the base color and emissive slots which multiply their factors.
+    pub default_texture: Arc<Texture>,
+    /// White without sRGB decoding, for the metallic-roughness and occlusion slots.
+    pub default_linear_texture: Arc<Texture>,
+    /// Tangent space normal pointing straight out of the surface.
+    pub default_normal_texture: Arc<Texture>,
+    /// Whether a texture used in both color spaces is uploaded once and viewed twice.
+    pub srgb_views: bool,
+    /// Maximum anisotropy of the samplers of imported textures, 1 disables it.
+    pub anisotropy_clamp: u16,
+    /// Blit pipelines filling the mip chains of imported textures.
+    pub mipmap_generator: Arc<MipmapGenerator>,
+    samplers: Mutex<HashMap<SamplerKey, Arc<wgpu::Sampler>>>,
+}
+
+impl MaterialRegistry {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let mut bind_group_layout_entries = (0..5)
+            .flat_map(|i| {
+                [
+                    wgpu::BindGroupLayoutEntry {
+                        binding: (i * 2) as u32,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: (i * 2 + 1) as u32,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+        bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 10,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
         });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("material"),
+            entries: bind_group_layout_entries.as_slice(),
+        });
+
+        Self {
+            bind_group_layout: Arc::new(bind_group_layout),
+            morph_targets_bind_group_layout: Arc::new(MorphTargets::bind_group_layout(device)),
+            default_texture: Arc::new(TextureBuilder::from_color(
+                device,
+                queue,
+                Rgba([255, 255, 255, 255]),
+            )),
+            default_linear_texture: Arc::new(TextureBuilder::from_linear_color(
+                device,
+                queue,
+                Rgba([255, 255, 255, 255]),
+            )),
+            default_normal_texture: Arc::new(TextureBuilder::from_linear_color(
+                device,
+                queue,
+                Rgba([128, 128, 255, 255]),
+            )),
+            srgb_views: false,
+            anisotropy_clamp: 1,
+            mipmap_generator: Arc::new(MipmapGenerator::new(device)),
+            samplers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Uploads textures once for both color spaces, needs `wgpu::DownlevelFlags::VIEW_FORMATS`.
+    pub fn with_srgb_views(mut self, srgb_views: bool) -> Self {
+        self.srgb_views = srgb_views;
+        self
+    }
+
+    pub fn with_mipmap_generator(mut self, mipmap_generator: MipmapGenerator) -> Self {
+        self.mipmap_generator = Arc::new(mipmap_generator);
+        self
+    }
 
-        Texture {
-            texture,
-            view,
-            sampler,
-        }
+    /// Filters imported textures anisotropically, up to `anisotropy_clamp` samples.
+    pub fn with_anisotropy_clamp(mut self, anisotropy_clamp: u16) -> Self {
+        self.anisotropy_clamp = anisotropy_clamp;
+        self
     }
 
-    pub fn from_color(device: &wgpu::Device, queue: &wgpu::Queue, rgba: Rgba<u8>) -> Texture {
-        let mut image_buffer = image::RgbaImage::new(1, 1);
-        image_buffer.put_pixel(0, 0, rgba);
-        Self::default().build(device, queue, image_buffer)
+    /// Sampler with the filters, address modes and anisotropy of `builder`, created the first
+    /// time they are asked for.
+    pub fn sampler(&self, device: &wgpu::Device, builder: &TextureBuilder) -> Arc<wgpu::Sampler> {
+        let key = builder.sampler_key();
+        self.samplers
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(key.create(device)))
+            .clone()
     }
 }
 
 pub struct Material {
-    pub emissive_factor: [f32; 3],
     pub emissive_tex_coord: u32,
+    pub emissive_transform: TextureTransform,
     pub emissive_texture: Arc<Texture>,
 
-    pub normal_scale: f32,
     pub normal_tex_coord: u32,
+    pub normal_transform: TextureTransform,
     pub normal_texture: Arc<Texture>,
 
-    pub occlusion_strength: f32,
     pub occlusion_tex_coord: u32,
+    pub occlusion_transform: TextureTransform,
     pub occlusion_texture: Arc<Texture>,
 
-    pub base_color_factor: [f32; 4],
     pub base_color_tex_coord: u32,
+    pub base_color_transform: TextureTransform,
     pub base_color_texture: Arc<Texture>,
 
-    pub metallic_factor: f32,
-    pub roughness_factor: f32,
     pub metallic_roughness_tex_coord: u32,
+    pub metallic_roughness_transform: TextureTransform,
     pub metallic_roughness_texture: Arc<Texture>,
 
-    pub material_bind_group_layout: wgpu::BindGroupLayout,
+    factors: RwLock<MaterialFactors>,
+
+    pub material_buffer: wgpu::Buffer,
     pub material_bind_group: wgpu::BindGroup,
 }
 
-#[derive(Default)]
+impl Material {
+    pub fn factors(&self) -> MaterialFactors {
+        *self.factors.read().unwrap()
+    }
+
+    /// Replaces the factors and uploads them, materials are shared between primitives so every
+    /// primitive using this one changes.
+    pub fn set_factors(&self, queue: &wgpu::Queue, factors: MaterialFactors) {
+        *self.factors.write().unwrap() = factors;
+        self.write_uniform(queue);
+    }
+
+    fn write_uniform(&self, queue: &wgpu::Queue) {
+        let factors = self.factors();
+        let uniform = MaterialUniform {
+            base_color_factor: factors.base_color,
+            emissive_factor: factors.emissive,
+            metallic_factor: factors.metallic,
+            roughness_factor: factors.roughness,
+            occlusion_strength: factors.occlusion_strength,
+            normal_scale: factors.normal_scale,
+            _padding: 0.0,
+            emissive_transform: self.emissive_transform.uniform(),
+            normal_transform: self.normal_transform.uniform(),
+            occlusion_transform: self.occlusion_transform.uniform(),
+            base_color_transform: self.base_color_transform.uniform(),
+            metallic_roughness_transform: self.metallic_roughness_transform.uniform(),
+            emissive_tex_coord: self.emissive_tex_coord,
+            normal_tex_coord: self.normal_tex_coord,
+            occlusion_tex_coord: self.occlusion_tex_coord,
+            base_color_tex_coord: self.base_color_tex_coord,
+            metallic_roughness_tex_coord: self.metallic_roughness_tex_coord,
+            _tex_coord_padding: [0; 3],
+        };
+        queue.write_buffer(&self.material_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
+
+/// Scalar parameters of a material, they multiply the values sampled from its textures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialFactors {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 3],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    base_color_factor: [f32; 4],
+    emissive_factor: [f32; 3],
+    metallic_factor: f32,
+    roughness_factor: f32,
+    occlusion_strength: f32,
+    normal_scale: f32,
+    _padding: f32,
+    emissive_transform: [[f32; 4]; 3],
+    normal_transform: [[f32; 4]; 3],
+    occlusion_transform: [[f32; 4]; 3],
+    base_color_transform: [[f32; 4]; 3],
+    metallic_roughness_transform: [[f32; 4]; 3],
+    emissive_tex_coord: u32,
+    normal_tex_coord: u32,
+    occlusion_tex_coord: u32,
+    base_color_tex_coord: u32,
+    metallic_roughness_tex_coord: u32,
+    _tex_coord_padding: [u32; 3],
+}
+
+/// UV offset, rotation and scale applied to the texture coordinates of a material slot before
+/// sampling, as defined by `KHR_texture_transform`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    /// Counter-clockwise rotation of the UVs in radians.
+    pub rotation: f32,
+    pub scale: [f32; 2],
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: [0.0; 2],
+            rotation: 0.0,
+            scale: [1.0; 2],
+        }
+    }
+}
+
+impl TextureTransform {
+    /// Translation times rotation times scale, applied to UVs as homogeneous 2D points.
+    pub fn matrix(&self) -> Matrix3<f32> {
+        let [offset_x, offset_y] = self.offset;
+        let [scale_x, scale_y] = self.scale;
+        let (sin, cos) = self.rotation.sin_cos();
+        Matrix3::new(1.0, 0.0, offset_x, 0.0, 1.0, offset_y, 0.0, 0.0, 1.0)
+            * Matrix3::new(cos, sin, 0.0, -sin, cos, 0.0, 0.0, 0.0, 1.0)
+            * Matrix3::new(scale_x, 0.0, 0.0, 0.0, scale_y, 0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Columns of the matrix padded to the 16 bytes WGSL aligns `mat3x3` columns to.
+    fn uniform(&self) -> [[f32; 4]; 3] {
+        let matrix = self.matrix();
+        [0, 1, 2].map(|column| {
+            let column = matrix.column(column);
+            [column[0], column[1], column[2], 0.0]
+        })
+    }
+}
+
+impl From<gltf::texture::TextureTransform<'_>> for TextureTransform {
+    fn from(transform: gltf::texture::TextureTransform<'_>) -> Self {
+        Self {
+            offset: transform.offset(),
+            rotation: transform.rotation(),
+            scale: transform.scale(),
+        }
+    }
+}
+
+impl From<gltf::json::extensions::texture::TextureTransform> for TextureTransform {
+    fn from(transform: gltf::json::extensions::texture::TextureTransform) -> Self {
+        Self {
+            offset: transform.offset.0,
+            rotation: transform.rotation.0,
+            scale: transform.scale.0,
+        }
+    }
+}
+
 pub struct MaterialBuilder {
     emissive_factor: [f32; 3],
     emissive_tex_coord: u32,
+    emissive_transform: TextureTransform,
     emissive_texture: Option<Arc<Texture>>,
 
     normal_scale: f32,
     normal_tex_coord: u32,
+    normal_transform: TextureTransform,
     normal_texture: Option<Arc<Texture>>,
 
     occlusion_strength: f32,
     occlusion_tex_coord: u32,
+    occlusion_transform: TextureTransform,
     occlusion_texture: Option<Arc<Texture>>,
 
     base_color_factor: [f32; 4],
     base_color_tex_coord: u32,
+    base_color_transform: TextureTransform,
     base_color_texture: Option<Arc<Texture>>,
 
     metallic_factor: f32,
     roughness_factor: f32,
     metallic_roughness_tex_coord: u32,
+    metallic_roughness_transform: TextureTransform,
     metallic_roughness_texture: Option<Arc<Texture>>,
 }
 
+impl Default for MaterialBuilder {
+    /// Starts from the glTF default material, a fully rough white metal.
+    fn default() -> Self {
+        Self {
+            emissive_factor: [0.0; 3],
+            emissive_tex_coord: 0,
+            emissive_transform: TextureTransform::default(),
+            emissive_texture: None,
+            normal_scale: 1.0,
+            normal_tex_coord: 0,
+            normal_transform: TextureTransform::default(),
+            normal_texture: None,
+            occlusion_strength: 1.0,
+            occlusion_tex_coord: 0,
+            occlusion_transform: TextureTransform::default(),
+            occlusion_texture: None,
+            base_color_factor: [1.0; 4],
+            base_color_tex_coord: 0,
+            base_color_transform: TextureTransform::default(),
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_tex_coord: 0,
+            metallic_roughness_transform: TextureTransform::default(),
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
 impl MaterialBuilder {
+    pub fn with_emissive_factor(&mut self, emissive_factor: [f32; 3]) -> &Self {
+        self.emissive_factor = emissive_factor;
+        self
+    }
+
     pub fn with_emissive(
         &mut self,
         emissive_factor: [f32; 3],
@@ -436,6 +1910,11 @@ impl MaterialBuilder {
         self
     }
 
+    pub fn with_base_color_factor(&mut self, base_color_factor: [f32; 4]) -> &Self {
+        self.base_color_factor = base_color_factor;
+        self
+    }
+
     pub fn with_base_color(
         &mut self,
         base_color_factor: [f32; 4],
@@ -448,6 +1927,16 @@ impl MaterialBuilder {
         self
     }
 
+    pub fn with_metallic_roughness_factors(
+        &mut self,
+        metallic_factor: f32,
+        roughness_factor: f32,
+    ) -> &Self {
+        self.metallic_factor = metallic_factor;
+        self.roughness_factor = roughness_factor;
+        self
+    }
+
     pub fn with_metallic_roughness(
         &mut self,
         metallic_factor: f32,
@@ -462,62 +1951,65 @@ impl MaterialBuilder {
         self
     }
 
-    pub fn build(self, device: &wgpu::Device, queue: &wgpu::Queue) -> Material {
-        let default_texture = Arc::new(TextureBuilder::from_color(
-            device,
-            queue,
-            Rgba([255, 255, 255, 255]),
-        ));
-        let emissive_texture = match self.emissive_texture {
-            Some(tex) => tex.clone(),
-            None => default_texture.clone(),
-        };
-        let normal_texture = match self.normal_texture {
-            Some(tex) => tex.clone(),
-            None => default_texture.clone(),
-        };
-        let occlusion_texture = match self.occlusion_texture {
-            Some(tex) => tex.clone(),
-            None => default_texture.clone(),
-        };
-        let base_color_texture = match self.base_color_texture {
-            Some(tex) => tex.clone(),
-            None => default_texture.clone(),
-        };
-        let metallic_roughness_texture = match self.metallic_roughness_texture {
-            Some(tex) => tex.clone(),
-            None => default_texture.clone(),
-        };
-        let material_bind_group_layout_entries = (0..5)
-            .flat_map(|i| {
-                [
-                    wgpu::BindGroupLayoutEntry {
-                        binding: (i * 2) as u32,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: (i * 2 + 1) as u32,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ]
-            })
-            .collect::<Vec<_>>();
-        let material_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("material bind group layout"),
-                entries: material_bind_group_layout_entries.as_slice(),
-            });
+    pub fn with_emissive_transform(&mut self, emissive_transform: TextureTransform) -> &Self {
+        self.emissive_transform = emissive_transform;
+        self
+    }
+
+    pub fn with_normal_transform(&mut self, normal_transform: TextureTransform) -> &Self {
+        self.normal_transform = normal_transform;
+        self
+    }
+
+    pub fn with_occlusion_transform(&mut self, occlusion_transform: TextureTransform) -> &Self {
+        self.occlusion_transform = occlusion_transform;
+        self
+    }
+
+    pub fn with_base_color_transform(&mut self, base_color_transform: TextureTransform) -> &Self {
+        self.base_color_transform = base_color_transform;
+        self
+    }
+
+    pub fn with_metallic_roughness_transform(
+        &mut self,
+        metallic_roughness_transform: TextureTransform,
+    ) -> &Self {
+        self.metallic_roughness_transform = metallic_roughness_transform;
+        self
+    }
+
+    /// Unset texture slots use the defaults of `registry`, the bind group follows its layout.
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        registry: &MaterialRegistry,
+    ) -> Material {
+        let emissive_texture = self
+            .emissive_texture
+            .unwrap_or_else(|| registry.default_texture.clone());
+        let normal_texture = self
+            .normal_texture
+            .unwrap_or_else(|| registry.default_normal_texture.clone());
+        let occlusion_texture = self
+            .occlusion_texture
+            .unwrap_or_else(|| registry.default_linear_texture.clone());
+        let base_color_texture = self
+            .base_color_texture
+            .unwrap_or_else(|| registry.default_texture.clone());
+        let metallic_roughness_texture = self
+            .metallic_roughness_texture
+            .unwrap_or_else(|| registry.default_linear_texture.clone());
+        let material_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("material"),
+            size: std::mem::size_of::<MaterialUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
         let material_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("material bind group"),
-            layout: &material_bind_group_layout,
+            layout: &registry.bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -563,29 +2055,42 @@ impl MaterialBuilder {
                         &metallic_roughness_texture.as_ref().sampler,
                     ),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: material_buffer.as_entire_binding(),
+                },
             ],
         });
 
-        Material {
-            emissive_factor: self.emissive_factor,
+        let material = Material {
             emissive_tex_coord: self.emissive_tex_coord,
+            emissive_transform: self.emissive_transform,
             emissive_texture,
-            normal_scale: self.normal_scale,
             normal_tex_coord: self.normal_tex_coord,
+            normal_transform: self.normal_transform,
             normal_texture,
-            occlusion_strength: self.occlusion_strength,
             occlusion_tex_coord: self.occlusion_tex_coord,
+            occlusion_transform: self.occlusion_transform,
             occlusion_texture,
-            base_color_factor: self.base_color_factor,
             base_color_tex_coord: self.base_color_tex_coord,
+            base_color_transform: self.base_color_transform,
             base_color_texture,
-            metallic_factor: self.metallic_factor,
-            roughness_factor: self.roughness_factor,
             metallic_roughness_tex_coord: self.metallic_roughness_tex_coord,
+            metallic_roughness_transform: self.metallic_roughness_transform,
             metallic_roughness_texture,
-            material_bind_group_layout,
+            factors: RwLock::new(MaterialFactors {
+                base_color: self.base_color_factor,
+                emissive: self.emissive_factor,
+                metallic: self.metallic_factor,
+                roughness: self.roughness_factor,
+                normal_scale: self.normal_scale,
+                occlusion_strength: self.occlusion_strength,
+            }),
+            material_buffer,
             material_bind_group,
-        }
+        };
+        material.write_uniform(queue);
+        material
     }
 }
 
@@ -593,15 +2098,407 @@ pub struct Mesh {
     pub primitives: Vec<Arc<Primitive>>,
 }
 
-#[derive(Default)]
+impl Mesh {
+    /// Number of morph targets, the largest one among the primitives.
+    pub fn target_count(&self) -> usize {
+        self.primitives
+            .iter()
+            .map(|primitive| primitive.morph_targets.target_count as usize)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A node of the scene graph, its transform is relative to the parent node and the world matrix
+/// is cached until `update_world_matrices` runs after a change.
 pub struct Node {
-    pub transform_matrix: Matrix4<f32>,
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>,
+    world_matrix: Matrix4<f32>,
+    dirty: bool,
     pub mesh: Option<Arc<Mesh>>,
+    pub skin: Option<Arc<Skin>>,
+    pub light: Option<Light>,
+    pub camera: Option<Projection>,
+    /// Morph target weights of the node's mesh.
+    pub weights: Vec<f32>,
+    rest_pose: Pose,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::repeat(1.0),
+            world_matrix: Matrix4::identity(),
+            dirty: true,
+            mesh: None,
+            skin: None,
+            light: None,
+            camera: None,
+            weights: Vec::new(),
+            rest_pose: Pose {
+                translation: Vector3::zeros(),
+                rotation: UnitQuaternion::identity(),
+                scale: Vector3::repeat(1.0),
+                weights: Vec::new(),
+            },
+        }
+    }
+}
+
+impl Node {
+    pub const fn translation(&self) -> &Vector3<f32> {
+        &self.translation
+    }
+
+    pub const fn rotation(&self) -> &UnitQuaternion<f32> {
+        &self.rotation
+    }
+
+    pub const fn scale(&self) -> &Vector3<f32> {
+        &self.scale
+    }
+
+    /// Transform of the node relative to the root of the scene as of the last update.
+    pub const fn world_matrix(&self) -> &Matrix4<f32> {
+        &self.world_matrix
+    }
+
+    pub fn local_matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    pub fn set_translation(&mut self, translation: Vector3<f32>) {
+        self.translation = translation;
+        self.dirty = true;
+    }
+
+    pub fn set_rotation(&mut self, rotation: UnitQuaternion<f32>) {
+        self.rotation = rotation;
+        self.dirty = true;
+    }
+
+    pub fn set_scale(&mut self, scale: Vector3<f32>) {
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    pub fn with_translation(mut self, translation: Vector3<f32>) -> Self {
+        self.set_translation(translation);
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuaternion<f32>) -> Self {
+        self.set_rotation(rotation);
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.set_scale(scale);
+        self
+    }
+
+    /// Transform and morph target weights the node has when no animation drives it, nodes an
+    /// animation stops driving fade back to it.
+    pub const fn rest_pose(&self) -> &Pose {
+        &self.rest_pose
+    }
+
+    pub fn with_rest_pose(mut self, rest_pose: Pose) -> Self {
+        self.rest_pose = rest_pose;
+        self
+    }
+}
+
+pub struct Skin {
+    pub joints: Vec<NodeIndex>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
 }
 
 pub struct Primitive {
+    pub topology: wgpu::PrimitiveTopology,
     pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
+    pub vertex_count: u32,
+    /// Non-indexed primitives draw their vertices in order.
+    pub index_buffer: Option<wgpu::Buffer>,
     pub index_count: u32,
+    /// Vertex colors of the `COLOR_1` and later sets, `COLOR_0` is in the vertex buffer.
+    pub colors: Vec<Vec<[f32; 4]>>,
     pub material: Arc<Material>,
+    pub morph_targets: MorphTargets,
+}
+
+impl Primitive {
+    /// Binds the vertex and index buffers and draws the primitive, the pipeline matching its
+    /// topology must already be set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if let Some(index_buffer) = &self.index_buffer {
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+        } else {
+            render_pass.draw(0..self.vertex_count, 0..1);
+        }
+    }
+}
+
+/// Position and normal displacement of a vertex in a morph target.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MorphDelta {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+}
+
+/// Displacements of all the morph targets of a primitive in a storage buffer, target after
+/// target with one delta per vertex.
+pub struct MorphTargets {
+    pub target_count: u32,
+    pub deltas_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl MorphTargets {
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("morph targets"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        deltas: &[MorphDelta],
+        vertex_count: u32,
+        target_count: u32,
+    ) -> Self {
+        // Storage buffers can't be empty, primitives without targets get a single zero delta.
+        let default_deltas = [MorphDelta::default()];
+        let deltas_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("morph deltas"),
+                contents: bytemuck::cast_slice(if deltas.is_empty() {
+                    &default_deltas
+                } else {
+                    deltas
+                }),
+                usage: wgpu::BufferUsages::STORAGE,
+            },
+        );
+        let counts_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("morph target counts"),
+                contents: bytemuck::cast_slice(&[vertex_count, target_count, 0, 0]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("morph targets"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: deltas_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: counts_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            target_count,
+            deltas_buffer,
+            bind_group,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point2, Vector2};
+
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        actual.iter().zip(expected).for_each(|(actual, expected)| {
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "{actual:?} != {expected:?}"
+            );
+        });
+    }
+
+    #[test]
+    fn texture_transforms_scale_then_rotate_then_offset() {
+        let transform = TextureTransform {
+            offset: [0.5, 0.25],
+            rotation: std::f32::consts::FRAC_PI_2,
+            scale: [2.0, 3.0],
+        };
+        let uv = |u, v| {
+            let uv = transform.matrix().transform_point(&Point2::new(u, v));
+            [uv.x, uv.y]
+        };
+        // Rotating counter-clockwise in UV space, whose V axis points down the image.
+        assert_close(&uv(1.0, 0.0), &[0.5, -1.75]);
+        assert_close(&uv(0.0, 1.0), &[3.5, 0.25]);
+        let direction = transform.matrix().transform_vector(&Vector2::new(1.0, 1.0));
+        assert_close(direction.as_slice(), &[3.0, -2.0]);
+
+        assert_eq!(TextureTransform::default().matrix(), Matrix3::identity());
+    }
+
+    #[test]
+    fn fans_and_loops_become_lists_and_strips() {
+        let (list, indices) = topology(gltf::mesh::Mode::TriangleFan, None, 5);
+        assert_eq!(list, wgpu::PrimitiveTopology::TriangleList);
+        assert_eq!(indices.unwrap(), [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        let (_, indices) = topology(gltf::mesh::Mode::TriangleFan, Some(vec![4, 2, 0, 1]), 5);
+        assert_eq!(indices.unwrap(), [4, 2, 0, 4, 0, 1]);
+
+        let (strip, indices) = topology(gltf::mesh::Mode::LineLoop, None, 3);
+        assert_eq!(strip, wgpu::PrimitiveTopology::LineStrip);
+        assert_eq!(indices.unwrap(), [0, 1, 2, 0]);
+        let (_, indices) = topology(gltf::mesh::Mode::LineLoop, Some(vec![2, 1]), 3);
+        assert_eq!(indices.unwrap(), [2, 1, 2]);
+    }
+
+    #[test]
+    fn other_topologies_keep_their_indices() {
+        for (mode, expected) in [
+            (gltf::mesh::Mode::Points, wgpu::PrimitiveTopology::PointList),
+            (gltf::mesh::Mode::Lines, wgpu::PrimitiveTopology::LineList),
+            (
+                gltf::mesh::Mode::LineStrip,
+                wgpu::PrimitiveTopology::LineStrip,
+            ),
+            (
+                gltf::mesh::Mode::Triangles,
+                wgpu::PrimitiveTopology::TriangleList,
+            ),
+            (
+                gltf::mesh::Mode::TriangleStrip,
+                wgpu::PrimitiveTopology::TriangleStrip,
+            ),
+        ] {
+            assert_eq!(topology(mode, None, 4), (expected, None));
+            assert_eq!(
+                topology(mode, Some(vec![3, 2, 1, 0]), 4),
+                (expected, Some(vec![3, 2, 1, 0]))
+            );
+        }
+    }
+
+    #[test]
+    fn import_errors_tell_io_from_parse_failures() {
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(matches!(
+            ImportError::from(gltf::Error::Io(missing)),
+            ImportError::Io(_)
+        ));
+        assert!(matches!(
+            ImportError::from(gltf::Error::UnsupportedScheme),
+            ImportError::Parse(gltf::Error::UnsupportedScheme)
+        ));
+        assert!(matches!(
+            ImportError::from(Ktx2Error::Truncated),
+            ImportError::Ktx2(Ktx2Error::Truncated)
+        ));
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures");
+        assert!(matches!(
+            open(&dir.join("missing.gltf")),
+            Err(ImportError::Io(_))
+        ));
+        assert!(matches!(
+            open(&dir.join("invalid.gltf")),
+            Err(ImportError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn import_errors_name_the_failing_item() {
+        let unsupported = ImportError::Unsupported {
+            location: format!("image {}", label(None, 2)),
+            feature: "data URI encoding".to_string(),
+        };
+        assert_eq!(
+            unsupported.to_string(),
+            "image #2 uses unsupported data URI encoding"
+        );
+        let malformed = ImportError::MalformedAccessor {
+            location: format!("mesh {} primitive 0", label(Some("Cube"), 0)),
+            accessor: "NORMAL",
+        };
+        assert_eq!(
+            malformed.to_string(),
+            "mesh \"Cube\" primitive 0 has a malformed NORMAL accessor"
+        );
+        assert_eq!(
+            ImportError::MissingScene("named Lobby".to_string()).to_string(),
+            "no scene named Lobby in glTF file"
+        );
+    }
+
+    #[test]
+    fn deep_images_keep_their_precision() {
+        let data = |format, pixels: &[u8]| gltf::image::Data {
+            pixels: pixels.to_vec(),
+            format,
+            width: 1,
+            height: 1,
+        };
+        let rgb16 = [1u16, 2, 65535].map(u16::to_ne_bytes).concat();
+        assert_eq!(
+            dynamic_image(&data(gltf::image::Format::R16G16B16, &rgb16)),
+            Some(DynamicImage::ImageRgb16(
+                ImageBuffer::from_raw(1, 1, vec![1, 2, 65535]).unwrap()
+            ))
+        );
+        let rgba32 = [0.5f32, 2.0, -1.0, 1.0].map(f32::to_ne_bytes).concat();
+        assert_eq!(
+            dynamic_image(&data(gltf::image::Format::R32G32B32A32FLOAT, &rgba32)),
+            Some(DynamicImage::ImageRgba32F(
+                ImageBuffer::from_raw(1, 1, vec![0.5, 2.0, -1.0, 1.0]).unwrap()
+            ))
+        );
+        assert_eq!(
+            dynamic_image(&data(gltf::image::Format::R16, &[0xFF])),
+            None
+        );
+    }
 }
@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use legion::world::SubWorld;
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::{
+    camera::Projection,
+    geometry::{DeformationBindings, Deformations, VertexAttribute},
+    graph::{Attachment, Pass},
+    lighting::{self, Light},
+    renderer::{RenderNode, RenderNodeBuilder},
+    scene::{self, MaterialRegistry, Mesh},
+};
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowFace {
+    view_projection: [[f32; 4]; 4],
+    light_position: [f32; 4],
+}
+
+/// Renders the distance to every shadow casting point light into the six faces of a cube, the
+/// cubes of all casters are stored in a single cube array texture.
+pub struct ShadowPass {
+    faces_buffer: wgpu::Buffer,
+    faces_bind_group_layout: wgpu::BindGroupLayout,
+    faces_bind_group: wgpu::BindGroup,
+    models_bind_group_layout: wgpu::BindGroupLayout,
+    models: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    deformations: Arc<Deformations>,
+    deformation_bindings: DeformationBindings,
+    morph_targets_bind_group_layout: Arc<wgpu::BindGroupLayout>,
+    meshes: Vec<Arc<Mesh>>,
+    caster_count: u32,
+    dropped_casters: usize,
+}
+
+impl ShadowPass {
+    pub const SHADOW_MAP: &'static str = "shadow_map";
+
+    pub const MAX_SHADOW_CASTERS: u32 = 4;
+    pub const SHADOW_MAP_SIZE: u32 = 512;
+    /// Shadow distance of point lights without a range, stored depths are the distance to the
+    /// light divided by the shadow distance.
+    pub const SHADOW_FAR: f32 = 100.0;
+
+    const SHADOW_MAP_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    const SHADOW_NEAR: f32 = 0.05;
+
+    /// Stride between dynamic uniform offsets, the default `min_uniform_buffer_offset_alignment`.
+    const UNIFORM_STRIDE: u64 = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        materials: &MaterialRegistry,
+        deformations: Arc<Deformations>,
+    ) -> Self {
+        let faces_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("shadow faces"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ShadowFace>() as u64
+                        ),
+                    },
+                    count: None,
+                }],
+            });
+        let faces_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow faces"),
+            size: u64::from(Self::MAX_SHADOW_CASTERS * 6) * Self::UNIFORM_STRIDE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let faces_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow faces"),
+            layout: &faces_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &faces_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ShadowFace>() as u64),
+                }),
+            }],
+        });
+
+        let models_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("shadow models"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Matrix4<f32>>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+            });
+
+        Self {
+            faces_buffer,
+            faces_bind_group_layout,
+            faces_bind_group,
+            models_bind_group_layout,
+            models: None,
+            deformations,
+            deformation_bindings: DeformationBindings::default(),
+            morph_targets_bind_group_layout: materials.morph_targets_bind_group_layout.clone(),
+            meshes: Vec::new(),
+            caster_count: 0,
+            dropped_casters: 0,
+        }
+    }
+
+    /// View projection matrices of the cube faces around a light, ordered +X, -X, +Y, -Y, +Z
+    /// and -Z like the layers of the shadow map.
+    ///
+    /// Cube map faces are addressed left-handed, the faces are rendered upside down to match,
+    /// which flips their winding.
+    fn face_view_projections(position: &Point3<f32>, far: f32) -> [Matrix4<f32>; 6] {
+        let projection = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, -1.0, 1.0))
+            * Projection::Perspective {
+                aspect: Some(1.0),
+                fovy: std::f32::consts::FRAC_PI_2,
+                znear: Self::SHADOW_NEAR,
+                zfar: Some(far),
+            }
+            .as_matrix();
+        [
+            (Vector3::x(), -Vector3::y()),
+            (-Vector3::x(), -Vector3::y()),
+            (Vector3::y(), Vector3::z()),
+            (-Vector3::y(), -Vector3::z()),
+            (Vector3::z(), -Vector3::y()),
+            (-Vector3::z(), -Vector3::y()),
+        ]
+        .map(|(direction, up)| {
+            projection * Matrix4::look_at_rh(position, &(position + direction), &up)
+        })
+    }
+
+    /// Positions and shadow distances of the shadow casting point lights, the first
+    /// `MAX_SHADOW_CASTERS` of them get the cubes of the shadow map in order.
+    fn casters(world: &SubWorld) -> Vec<(Point3<f32>, f32)> {
+        lighting::lights(world)
+            .iter()
+            .filter_map(|(light, transform_matrix)| match light {
+                Light::Point(light) if light.cast_shadows => Some((
+                    Point3::from(transform_matrix.column(3).xyz()),
+                    light.range.unwrap_or(Self::SHADOW_FAR),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Pass for ShadowPass {
+    fn name(&self) -> &'static str {
+        "shadow"
+    }
+
+    fn outputs(&self) -> Vec<Attachment> {
+        vec![
+            Attachment::new(Self::SHADOW_MAP, Self::SHADOW_MAP_TEXTURE_FORMAT)
+                .with_size(wgpu::Extent3d {
+                    width: Self::SHADOW_MAP_SIZE,
+                    height: Self::SHADOW_MAP_SIZE,
+                    depth_or_array_layers: Self::MAX_SHADOW_CASTERS * 6,
+                })
+                .with_view_dimension(wgpu::TextureViewDimension::CubeArray)
+                .with_sample_type(wgpu::TextureSampleType::Depth),
+        ]
+    }
+
+    fn front_face(&self) -> wgpu::FrontFace {
+        wgpu::FrontFace::Cw
+    }
+
+    fn configure<'a>(&'a self, builder: RenderNodeBuilder<'a>) -> RenderNodeBuilder<'a> {
+        builder
+            .with_shader_source(include_str!("shadow.wgsl").into())
+            .with_bind_group_layout(&self.faces_bind_group_layout)
+            .with_bind_group_layout(&self.models_bind_group_layout)
+            .with_bind_group_layout(&self.deformations.bind_group_layout)
+            .with_bind_group_layout(&self.morph_targets_bind_group_layout)
+            .with_vertex_buffer_layout(VertexAttribute::desc())
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &SubWorld) {
+        let mut casters = Self::casters(world);
+        let dropped_casters = casters
+            .len()
+            .saturating_sub(Self::MAX_SHADOW_CASTERS as usize);
+        if dropped_casters > 0 && dropped_casters != self.dropped_casters {
+            log::warn!(
+                "{} point lights cast shadows, only the first {} get a shadow map",
+                casters.len(),
+                Self::MAX_SHADOW_CASTERS
+            );
+        }
+        self.dropped_casters = dropped_casters;
+        casters.truncate(Self::MAX_SHADOW_CASTERS as usize);
+        self.caster_count = casters.len() as u32;
+        casters
+            .iter()
+            .flat_map(|(position, far)| {
+                Self::face_view_projections(position, *far).map(|view_projection| ShadowFace {
+                    view_projection: view_projection.into(),
+                    light_position: [position.x, position.y, position.z, *far],
+                })
+            })
+            .enumerate()
+            .for_each(|(layer, face)| {
+                queue.write_buffer(
+                    &self.faces_buffer,
+                    layer as u64 * Self::UNIFORM_STRIDE,
+                    bytemuck::bytes_of(&face),
+                );
+            });
+
+        let instances = scene::meshes(world);
+        self.meshes = instances
+            .iter()
+            .map(|instance| instance.mesh.clone())
+            .collect();
+        if self.meshes.is_empty() || casters.is_empty() {
+            return;
+        }
+        self.deformation_bindings = self.deformations.bindings();
+
+        let size = instances.len() as u64 * Self::UNIFORM_STRIDE;
+        if !matches!(&self.models, Some((buffer, _)) if buffer.size() >= size) {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("shadow models"),
+                size,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("shadow models"),
+                layout: &self.models_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<Matrix4<f32>>() as u64),
+                    }),
+                }],
+            });
+            self.models = Some((buffer, bind_group));
+        }
+        let (buffer, _) = self.models.as_ref().unwrap();
+        instances.iter().enumerate().for_each(|(index, instance)| {
+            queue.write_buffer(
+                buffer,
+                index as u64 * Self::UNIFORM_STRIDE,
+                bytemuck::cast_slice(instance.model_matrix.as_slice()),
+            );
+        });
+    }
+
+    fn layers(&self) -> u32 {
+        self.caster_count * 6
+    }
+
+    /// Points and lines have no surface to cast shadows with.
+    fn topologies(&self) -> Vec<wgpu::PrimitiveTopology> {
+        vec![
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::PrimitiveTopology::TriangleStrip,
+        ]
+    }
+
+    fn execute<'a>(&'a self, node: &'a RenderNode, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.execute_layer(0, node, render_pass);
+    }
+
+    fn execute_layer<'a>(
+        &'a self,
+        layer: u32,
+        node: &'a RenderNode,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        let Some((_, models_bind_group)) = &self.models else {
+            return;
+        };
+        render_pass.set_bind_group(
+            0,
+            &self.faces_bind_group,
+            &[layer * Self::UNIFORM_STRIDE as u32],
+        );
+        self.meshes.iter().enumerate().for_each(|(index, mesh)| {
+            render_pass.set_bind_group(
+                1,
+                models_bind_group,
+                &[index as u32 * Self::UNIFORM_STRIDE as u32],
+            );
+            self.deformation_bindings.bind(render_pass, 2, index);
+            mesh.primitives.iter().for_each(|primitive| {
+                let Some(render_pipeline) = node.render_pipeline(primitive.topology) else {
+                    return;
+                };
+                render_pass.set_pipeline(render_pipeline);
+                render_pass.set_bind_group(3, &primitive.morph_targets.bind_group, &[]);
+                primitive.draw(render_pass);
+            });
+        });
+    }
+}
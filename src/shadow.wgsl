@@ -0,0 +1,75 @@
+struct ShadowFace {
+    view_projection : mat4x4<f32>,
+    // World space position of the light, `w` holds the far distance depths are divided by.
+    light_position  : vec4<f32>,
+}
+@group(0) @binding(0) var<uniform> face : ShadowFace;
+@group(1) @binding(0) var<uniform> model_matrix : mat4x4<f32>;
+
+// Joint matrices of the skin the vertices follow, already holding the world transform.
+@group(2) @binding(0) var<storage, read> joint_matrices : array<mat4x4<f32>>;
+
+fn skin_matrix(joints : vec4<u32>, weights : vec4<f32>) -> mat4x4<f32> {
+    return weights.x * joint_matrices[joints.x]
+         + weights.y * joint_matrices[joints.y]
+         + weights.z * joint_matrices[joints.z]
+         + weights.w * joint_matrices[joints.w];
+}
+
+@group(2) @binding(1) var<storage, read> morph_weights : array<f32>;
+
+struct MorphDelta {
+    position : vec4<f32>,
+    normal   : vec4<f32>,
+}
+struct MorphTargetCounts {
+    vertex_count : u32,
+    target_count : u32,
+}
+// Displacements of the primitive's morph targets, target after target with one per vertex.
+@group(3) @binding(0) var<storage, read> morph_deltas : array<MorphDelta>;
+@group(3) @binding(1) var<uniform> morph_target_counts : MorphTargetCounts;
+
+fn morph_delta(vertex_index : u32) -> MorphDelta {
+    var delta : MorphDelta;
+    for (var index = 0u; index < morph_target_counts.target_count; index++) {
+        let target_delta = morph_deltas[index * morph_target_counts.vertex_count + vertex_index];
+        delta.position += morph_weights[index] * target_delta.position;
+        delta.normal += morph_weights[index] * target_delta.normal;
+    }
+    return delta;
+}
+
+struct VertexOutput {
+    @builtin(position) position : vec4<f32>,
+    @location(0) world_position : vec3<f32>,
+}
+
+@vertex fn vertex(
+    @builtin(vertex_index) vertex_index : u32,
+    @location(0) in_position    : vec3<f32>,
+    @location(1) in_normal      : vec3<f32>,
+    @location(2) in_color_0     : vec4<f32>,
+    @location(3) in_tex_coord_0 : vec2<f32>,
+    @location(4) in_tangent     : vec4<f32>,
+    @location(5) in_joints_0    : vec4<u32>,
+    @location(6) in_weights_0   : vec4<f32>,
+    @location(7) in_tex_coord_1 : vec2<f32>,
+) -> VertexOutput {
+    var world_matrix = model_matrix;
+    if dot(in_weights_0, vec4(1.0)) > 0.0 {
+        world_matrix = skin_matrix(in_joints_0, in_weights_0);
+    }
+    let delta = morph_delta(vertex_index);
+    let world_position = world_matrix * vec4(in_position + delta.position.xyz, 1.0);
+
+    var out : VertexOutput;
+    out.position = face.view_projection * world_position;
+    out.world_position = world_position.xyz;
+
+    return out;
+}
+
+@fragment fn fragment(in : VertexOutput) -> @builtin(frag_depth) f32 {
+    return length(in.world_position - face.light_position.xyz) / face.light_position.w;
+}
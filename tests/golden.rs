@@ -0,0 +1,232 @@
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use legion::World;
+use nalgebra::{Matrix4, Translation3, Vector3};
+use obscura::{
+    camera::{Projection, View},
+    lighting::PointLight,
+    renderer::Renderer,
+    scene,
+};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+const MODELS: [&str; 9] = [
+    "BoxTextured",
+    "BoxVertexColors",
+    "cone",
+    "cube",
+    "cylinder",
+    "icosphere",
+    "plane",
+    "suzanne",
+    "torus",
+];
+
+/// Maximum YIQ color distance, as a fraction of the largest possible one, for two pixels to be
+/// considered equal.
+const PIXEL_THRESHOLD: f32 = 0.1;
+/// Fraction of pixels allowed to differ before an image is considered a mismatch.
+const MISMATCH_TOLERANCE: f32 = 0.005;
+
+/// Renders every bundled model and compares it against its reference image in `tests/golden`.
+///
+/// Set `OBSCURA_BLESS=1` to overwrite the references with the current output instead.
+#[test]
+fn golden_images() {
+    // Skipping would let a runner without any adapter pass while testing nothing.
+    let mut renderer = Renderer::headless(WIDTH, HEIGHT)
+        .expect("no graphics adapter available, install a software one such as llvmpipe");
+    let bless = std::env::var_os("OBSCURA_BLESS").is_some();
+    let diff_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&diff_dir).unwrap();
+
+    let failures = MODELS
+        .iter()
+        .filter_map(|&model| {
+            let actual = render_model(&mut renderer, model);
+            check(model, &actual, bless, &diff_dir)
+        })
+        .collect::<Vec<_>>();
+
+    assert!(
+        failures.is_empty(),
+        "golden image mismatches:\n{}",
+        failures.join("\n")
+    );
+}
+
+/// Renders a cube floating over a floor in front of a wall, lit from three sides so that the
+/// shadows fall on different faces of the light's shadow cube.
+#[test]
+fn golden_shadows() {
+    let mut renderer = Renderer::headless(WIDTH, HEIGHT)
+        .expect("no graphics adapter available, install a software one such as llvmpipe");
+    let bless = std::env::var_os("OBSCURA_BLESS").is_some();
+    let diff_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&diff_dir).unwrap();
+
+    let failures = [
+        Translation3::new(0.5, 2.0, -4.0),
+        Translation3::new(3.0, 0.5, -5.0),
+        Translation3::new(0.0, 0.0, -2.5),
+    ]
+    .iter()
+    .enumerate()
+    .filter_map(|(index, light_translation)| {
+        let actual = render_shadows(&mut renderer, light_translation);
+        check(&format!("shadows_{index}"), &actual, bless, &diff_dir)
+    })
+    .collect::<Vec<_>>();
+
+    assert!(
+        failures.is_empty(),
+        "golden image mismatches:\n{}",
+        failures.join("\n")
+    );
+}
+
+/// Compares an image against its reference, or overwrites the reference when blessing, returns
+/// a description of the mismatch if any.
+fn check(name: &str, actual: &RgbaImage, bless: bool, diff_dir: &Path) -> Option<String> {
+    let reference_path = reference_path(name);
+    if bless {
+        actual.save(&reference_path).unwrap();
+        return None;
+    }
+
+    let Ok(reference) = image::open(&reference_path) else {
+        return Some(format!(
+            "{name}: missing reference {}",
+            reference_path.display()
+        ));
+    };
+    let (mismatched, diff) = compare(&reference.to_rgba8(), actual);
+    let mismatch_ratio = mismatched as f32 / (WIDTH * HEIGHT) as f32;
+    if mismatch_ratio <= MISMATCH_TOLERANCE {
+        return None;
+    }
+
+    let actual_path = diff_dir.join(format!("{name}.actual.png"));
+    let diff_path = diff_dir.join(format!("{name}.diff.png"));
+    actual.save(&actual_path).unwrap();
+    diff.save(&diff_path).unwrap();
+    Some(format!(
+        "{name}: {:.2}% of pixels differ, see {} and {}",
+        mismatch_ratio * 100.0,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"))
+}
+
+fn import(renderer: &Renderer, model: &str) -> scene::Scene {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("res")
+        .join(format!("{model}.glb"));
+    scene::import(&renderer.device, &renderer.queue, &renderer.materials, path)
+        .unwrap()
+        .scene
+}
+
+fn render_model(renderer: &mut Renderer, model: &str) -> RgbaImage {
+    let mut world = World::default();
+    world.push((Projection::new(WIDTH, HEIGHT), View::default()));
+
+    world.push((
+        import(renderer, model),
+        Translation3::<f32>::new(0.0, 0.0, -5.0).to_homogeneous(),
+    ));
+
+    let light = PointLight {
+        color: [60.0, 60.0, 60.0, 0.0],
+        ..Default::default()
+    };
+    world.push((
+        light,
+        Translation3::<f32>::new(2.0, 3.0, 0.0).to_homogeneous(),
+    ));
+
+    renderer.render_to_image(&mut world).unwrap()
+}
+
+fn render_shadows(renderer: &mut Renderer, light_translation: &Translation3<f32>) -> RgbaImage {
+    let mut world = World::default();
+    world.push((Projection::new(WIDTH, HEIGHT), View::default()));
+
+    let floor = Translation3::new(0.0, -1.5, -5.0).to_homogeneous()
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(4.0, 1.0, 4.0));
+    world.push((import(renderer, "plane"), floor));
+    let wall = Translation3::new(0.0, 0.0, -8.0).to_homogeneous()
+        * Matrix4::from_euler_angles(std::f32::consts::FRAC_PI_2, 0.0, 0.0)
+        * Matrix4::new_scaling(6.0);
+    world.push((import(renderer, "plane"), wall));
+    let cube = Translation3::new(0.3, -0.3, -5.0).to_homogeneous() * Matrix4::new_scaling(0.4);
+    world.push((import(renderer, "cube"), cube));
+
+    let light = PointLight {
+        color: [20.0, 20.0, 20.0, 0.0],
+        ..Default::default()
+    };
+    world.push((light, light_translation.to_homogeneous()));
+
+    renderer.render_to_image(&mut world).unwrap()
+}
+
+/// Compares two images with a perceptual YIQ color distance, returns the number of mismatched
+/// pixels and a diff image highlighting them in red over a faded copy of the reference.
+fn compare(reference: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    if reference.dimensions() != actual.dimensions() {
+        diff.pixels_mut()
+            .for_each(|pixel| *pixel = Rgba([255, 0, 0, 255]));
+        return (diff.len() / 4, diff);
+    }
+
+    let max_delta = 35215.0 * PIXEL_THRESHOLD * PIXEL_THRESHOLD;
+    let mut mismatched = 0;
+    reference
+        .pixels()
+        .zip(actual.pixels())
+        .zip(diff.pixels_mut())
+        .for_each(|((expected, actual), diff)| {
+            if color_delta(expected, actual) > max_delta {
+                mismatched += 1;
+                *diff = Rgba([255, 0, 0, 255]);
+            } else {
+                let y = 255.0 - (255.0 - luma(expected)) * 0.1;
+                *diff = Rgba([y as u8, y as u8, y as u8, 255]);
+            }
+        });
+
+    (mismatched, diff)
+}
+
+fn rgb(pixel: &Rgba<u8>) -> [f32; 3] {
+    let [r, g, b, _] = pixel.0.map(f32::from);
+    [r, g, b]
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    let [r, g, b] = rgb(pixel);
+    r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_2
+}
+
+fn color_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let [r1, g1, b1] = rgb(a);
+    let [r2, g2, b2] = rgb(b);
+    let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+
+    let y = dr * 0.298_895_3 + dg * 0.586_622_5 + db * 0.114_482_2;
+    let i = dr * 0.595_978 - dg * 0.274_176_5 - db * 0.321_801_5;
+    let q = dr * 0.211_470_2 - dg * 0.522_617_2 + db * 0.311_147;
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
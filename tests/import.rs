@@ -0,0 +1,392 @@
+use std::{f32::consts::FRAC_1_SQRT_2, path::PathBuf, time::Duration};
+
+use nalgebra::{Matrix4, Vector3};
+use obscura::{
+    animation::{AnimationPlayer, Interpolation, Playback, Property},
+    camera::Projection,
+    lighting::Light,
+    renderer::Renderer,
+    scene::{self, ImportError, Imported, Scene, SceneSelection, TextureTransform},
+};
+use petgraph::stable_graph::NodeIndex;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn renderer() -> Renderer {
+    Renderer::headless(1, 1)
+        .expect("no graphics adapter available, install a software one such as llvmpipe")
+}
+
+fn import(renderer: &Renderer, name: &str) -> Result<Imported, ImportError> {
+    scene::import(
+        &renderer.device,
+        &renderer.queue,
+        &renderer.materials,
+        fixture(name),
+    )
+}
+
+fn import_scene(
+    renderer: &Renderer,
+    name: &str,
+    selection: SceneSelection<'_>,
+) -> Result<Imported, ImportError> {
+    scene::import_scene(
+        &renderer.device,
+        &renderer.queue,
+        &renderer.materials,
+        fixture(name),
+        selection,
+    )
+}
+
+fn texture_layout(texture: &scene::Texture) -> (wgpu::TextureFormat, [u32; 2], u32) {
+    let texture = &texture.texture;
+    (
+        texture.format(),
+        [texture.width(), texture.height()],
+        texture.mip_level_count(),
+    )
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    actual.iter().zip(expected).for_each(|(actual, expected)| {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    });
+}
+
+#[test]
+fn skins() {
+    let renderer = renderer();
+    let scene = import(&renderer, "blade.gltf").unwrap().scene;
+    let [blade, root, tip] = [0, 1, 2].map(NodeIndex::new);
+
+    let skin = scene[blade].skin.clone().unwrap();
+    assert_eq!(skin.joints, [root, tip]);
+    assert_eq!(
+        skin.inverse_bind_matrices[1],
+        Matrix4::new_translation(&Vector3::new(0.0, -2.0, 0.0))
+    );
+    assert!(scene[blade].mesh.is_some());
+    assert!(scene.contains_edge(root, tip));
+    assert_close(
+        scene[tip].world_matrix().column(3).as_slice(),
+        &[0.0, 2.0, 0.0, 1.0],
+    );
+}
+
+#[test]
+fn animations() {
+    let renderer = renderer();
+    let Imported {
+        mut scene,
+        animations: clips,
+    } = import(&renderer, "blade.gltf").unwrap();
+    let [blade, root, tip] = [0, 1, 2].map(NodeIndex::new);
+
+    let [clip] = clips.as_slice() else {
+        panic!("expected one clip, got {}", clips.len());
+    };
+    assert_eq!(clip.name.as_deref(), Some("Sway"));
+    assert_close(&[clip.duration], &[1.0]);
+    let channels = clip
+        .channels
+        .iter()
+        .map(|channel| (channel.target, channel.property, channel.interpolation))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        channels,
+        [
+            (root, Property::Translation, Interpolation::Linear),
+            (tip, Property::Rotation, Interpolation::Step),
+            (blade, Property::Weights, Interpolation::CubicSpline),
+        ]
+    );
+
+    let mut player = AnimationPlayer::new(Playback::new(clip.clone()));
+    player.advance(Duration::from_millis(500));
+    player.apply(&mut scene);
+    assert_close(scene[root].translation().as_slice(), &[0.5, 1.0, 0.0]);
+    assert_close(
+        scene[tip].rotation().coords.as_slice(),
+        &[0.0, 0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+    );
+    // Halfway between two keyframes without tangents.
+    assert_close(&scene[blade].weights, &[0.5]);
+}
+
+#[test]
+fn morph_targets() {
+    let renderer = renderer();
+    let scene = import(&renderer, "blade.gltf").unwrap().scene;
+    let blade = &scene[NodeIndex::new(0)];
+
+    assert_eq!(blade.mesh.as_ref().unwrap().target_count(), 1);
+    // The mesh weights stand in for the node's missing ones.
+    assert_eq!(blade.weights, [0.25]);
+}
+
+#[test]
+fn lights() {
+    let renderer = renderer();
+    let scene = import(&renderer, "lights.gltf").unwrap().scene;
+    let node = |index| &scene[NodeIndex::new(index)];
+
+    let Some(Light::Directional(directional)) = node(0).light else {
+        panic!("expected a directional light, got {:?}", node(0).light);
+    };
+    assert_eq!(directional.color, [1.0, 0.5, 0.25, 1.0]);
+    assert_eq!(directional.intensity, 3.0);
+    let Some(Light::Point(point)) = node(1).light else {
+        panic!("expected a point light, got {:?}", node(1).light);
+    };
+    assert_eq!((point.intensity, point.range), (20.0, Some(8.0)));
+    assert_close(
+        node(1).world_matrix().column(3).as_slice(),
+        &[0.0, 4.0, 0.0, 1.0],
+    );
+    let Some(Light::Spot(spot)) = node(2).light else {
+        panic!("expected a spot light, got {:?}", node(2).light);
+    };
+    assert_eq!((spot.inner_cone_angle, spot.outer_cone_angle), (0.25, 0.5));
+    assert_eq!(spot.range, None);
+}
+
+#[test]
+fn cameras() {
+    let renderer = renderer();
+    let scene = import(&renderer, "cameras.gltf").unwrap().scene;
+    let node = |index| &scene[NodeIndex::new(index)];
+
+    assert!(matches!(
+        node(0).camera,
+        Some(Projection::Perspective {
+            aspect: Some(aspect),
+            fovy,
+            znear,
+            zfar: Some(zfar),
+        }) if (aspect, fovy, znear, zfar) == (1.5, 0.8, 0.05, 50.0)
+    ));
+    assert_close(
+        node(0).world_matrix().column(3).as_slice(),
+        &[0.0, 0.0, 5.0, 1.0],
+    );
+    assert!(matches!(
+        node(1).camera,
+        Some(Projection::Perspective {
+            aspect: None,
+            zfar: None,
+            ..
+        })
+    ));
+    assert!(matches!(
+        node(2).camera,
+        Some(Projection::Orthographic { xmag, ymag, znear, zfar })
+            if (xmag, ymag, znear, zfar) == (3.0, 2.0, 0.5, 20.0)
+    ));
+}
+
+#[test]
+fn scenes() {
+    let renderer = renderer();
+    let indices = |scene: &Scene| {
+        let mut indices = scene
+            .node_indices()
+            .map(NodeIndex::index)
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices
+    };
+
+    // The default scene has two roots, both imported along with the child of the first one.
+    let night = import(&renderer, "scenes.gltf").unwrap().scene;
+    assert_eq!(indices(&night), [1, 2, 3]);
+    assert_close(
+        night[NodeIndex::new(2)].world_matrix().column(3).as_slice(),
+        &[1.0, 2.0, 0.0, 1.0],
+    );
+    assert_eq!(
+        scene::camera_instances(&night, &Matrix4::identity()).len(),
+        1
+    );
+
+    let day = import_scene(&renderer, "scenes.gltf", SceneSelection::Name("Day"))
+        .unwrap()
+        .scene;
+    assert_eq!(indices(&day), [0]);
+    let first = import_scene(&renderer, "scenes.gltf", SceneSelection::Index(0))
+        .unwrap()
+        .scene;
+    assert_eq!(indices(&first), [0]);
+}
+
+#[test]
+fn topologies() {
+    let renderer = renderer();
+    let scene = import(&renderer, "topologies.gltf").unwrap().scene;
+    let mesh = scene[NodeIndex::new(0)].mesh.clone().unwrap();
+    let primitives = mesh
+        .primitives
+        .iter()
+        .map(|primitive| {
+            (
+                primitive.topology,
+                primitive.index_buffer.is_some(),
+                primitive.index_count,
+                primitive.vertex_count,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        primitives,
+        [
+            (wgpu::PrimitiveTopology::TriangleList, false, 0, 6),
+            (wgpu::PrimitiveTopology::TriangleStrip, true, 4, 6),
+            // The 4 triangles of the fan, then the loop closed by its first vertex.
+            (wgpu::PrimitiveTopology::TriangleList, true, 12, 6),
+            (wgpu::PrimitiveTopology::LineStrip, true, 7, 6),
+            (wgpu::PrimitiveTopology::PointList, false, 0, 6),
+        ]
+    );
+}
+
+#[test]
+fn import_errors() {
+    let renderer = renderer();
+    assert!(matches!(
+        import(&renderer, "malformed.gltf"),
+        Err(ImportError::MalformedAccessor { location, accessor: "indices" })
+            if location == "mesh \"Broken\" primitive 0"
+    ));
+    assert!(matches!(
+        import(&renderer, "missing.gltf"),
+        Err(ImportError::Io(_))
+    ));
+    assert!(matches!(
+        import(&renderer, "invalid.gltf"),
+        Err(ImportError::Parse(_))
+    ));
+
+    assert!(matches!(
+        import_scene(&renderer, "scenes.gltf", SceneSelection::Name("Lobby")),
+        Err(ImportError::MissingScene(scene)) if scene == "named Lobby"
+    ));
+    assert!(matches!(
+        import_scene(&renderer, "scenes.gltf", SceneSelection::Index(2)),
+        Err(ImportError::MissingScene(scene)) if scene == "2"
+    ));
+}
+
+#[test]
+fn ktx2_textures() {
+    let renderer = renderer();
+    let scene = import(&renderer, "ktx2.gltf").unwrap().scene;
+    let mesh = scene[NodeIndex::new(0)].mesh.clone().unwrap();
+    let material = &mesh.primitives[0].material;
+
+    // Uploaded with the mip levels of the container, in the linear occlusion color space.
+    assert_eq!(
+        texture_layout(&material.occlusion_texture),
+        (wgpu::TextureFormat::Rgba8Unorm, [4, 4], 2)
+    );
+    // ETC1S with alpha is transcoded to ETC2 or BC3 when the device has them, RGBA8 otherwise.
+    let features = renderer.device.features();
+    let etc1s_format = if features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2) {
+        wgpu::TextureFormat::Etc2Rgba8UnormSrgb
+    } else if features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC) {
+        wgpu::TextureFormat::Bc3RgbaUnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    };
+    assert_eq!(
+        texture_layout(&material.base_color_texture),
+        (etc1s_format, [16, 8], 1)
+    );
+    // Both zstd supercompressed levels, decoded as sRGB for the emissive role.
+    assert_eq!(
+        texture_layout(&material.emissive_texture),
+        (wgpu::TextureFormat::Rgba8UnormSrgb, [4, 4], 2)
+    );
+    // UASTC is transcoded to ASTC when the device has it, RGBA8 otherwise, with the mip levels
+    // of the container.
+    let uastc_format = if features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC) {
+        wgpu::TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        }
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    };
+    assert_eq!(
+        texture_layout(&material.metallic_roughness_texture),
+        (uastc_format, [4, 4], 3)
+    );
+}
+
+#[test]
+fn texture_transforms_and_extra_attribute_sets() {
+    let renderer = renderer();
+    let scene = import(&renderer, "texture_transform.gltf").unwrap().scene;
+    let mesh = scene[NodeIndex::new(0)].mesh.clone().unwrap();
+    let primitive = &mesh.primitives[0];
+    let material = &primitive.material;
+
+    // The extension overrides the texture coordinates of the texture info.
+    assert_eq!(
+        material.base_color_transform,
+        TextureTransform {
+            offset: [0.5, 0.25],
+            rotation: 1.5,
+            scale: [2.0, 3.0],
+        }
+    );
+    assert_eq!(material.base_color_tex_coord, 1);
+    assert_eq!(
+        material.normal_transform,
+        TextureTransform {
+            offset: [0.1, 0.2],
+            ..TextureTransform::default()
+        }
+    );
+    assert_eq!(material.normal_tex_coord, 1);
+    // Only TEXCOORD_0 and TEXCOORD_1 are imported.
+    assert_eq!(material.occlusion_tex_coord, 0);
+    assert_eq!(material.occlusion_transform, TextureTransform::default());
+
+    assert_eq!(
+        primitive.colors,
+        [[
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+        ]]
+    );
+}
+
+#[test]
+fn deep_and_grayscale_images() {
+    let renderer = renderer();
+    let scene = import(&renderer, "images.gltf").unwrap().scene;
+    let mesh = scene[NodeIndex::new(0)].mesh.clone().unwrap();
+    let material = &mesh.primitives[0].material;
+
+    // 16-bit colors keep their precision, decoded to linear since half floats have no sRGB.
+    assert_eq!(
+        texture_layout(&material.base_color_texture),
+        (wgpu::TextureFormat::Rgba16Float, [2, 1], 1)
+    );
+    assert_eq!(
+        texture_layout(&material.metallic_roughness_texture),
+        (wgpu::TextureFormat::Rgba8Unorm, [2, 1], 1)
+    );
+}
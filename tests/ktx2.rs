@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use obscura::ktx2::Ktx2;
+
+/// Bytes of a file in `fixtures/basisu`. The KTX2 files there were encoded by the Basis
+/// Universal tool, the `.rgba` and `.astc` files next to them hold what its transcoder outputs
+/// for every level of them, base level first.
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("basisu")
+        .join(name);
+    std::fs::read(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+}
+
+const ETC1S: [&str; 2] = ["etc1s_rgb", "etc1s_rgba"];
+/// Zstd supercompressed with mipmaps, then a grayscale and alpha one without supercompression.
+const UASTC: [&str; 3] = ["uastc_rgb", "uastc_rgba", "uastc_la"];
+
+#[test]
+fn transcodes_etc1s_like_the_reference_transcoder() {
+    for name in ETC1S {
+        let ktx2 = Ktx2::parse(&fixture(&format!("{name}.ktx2")), wgpu::Features::empty());
+        let ktx2 = ktx2.unwrap();
+        assert_eq!(ktx2.format, wgpu::TextureFormat::Rgba8UnormSrgb, "{name}");
+        assert_eq!(ktx2.levels.len(), 6, "{name}");
+        assert!(
+            ktx2.levels.concat() == fixture(&format!("{name}.rgba")),
+            "{name} differs from the reference"
+        );
+    }
+}
+
+#[test]
+fn transcodes_uastc_like_the_reference_transcoder() {
+    for name in UASTC {
+        let bytes = fixture(&format!("{name}.ktx2"));
+        let rgba = Ktx2::parse(&bytes, wgpu::Features::empty()).unwrap();
+        assert_eq!(rgba.format, wgpu::TextureFormat::Rgba8UnormSrgb, "{name}");
+        assert!(
+            rgba.levels.concat() == fixture(&format!("{name}.rgba")),
+            "{name} differs from the reference RGBA8"
+        );
+
+        let astc = Ktx2::parse(&bytes, wgpu::Features::TEXTURE_COMPRESSION_ASTC).unwrap();
+        assert_eq!(
+            astc.format,
+            wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B4x4,
+                channel: wgpu::AstcChannel::UnormSrgb,
+            },
+            "{name}"
+        );
+        assert_eq!(astc.levels.len(), rgba.levels.len(), "{name}");
+        assert!(
+            astc.levels.concat() == fixture(&format!("{name}.astc")),
+            "{name} differs from the reference ASTC"
+        );
+    }
+}
+
+/// Alpha of every pixel of the EAC blocks of an ETC2 RGBA8 level.
+fn eac_alphas(level: &[u8], width: usize, height: usize) -> Vec<u8> {
+    const MODIFIERS: [[i32; 8]; 16] = [
+        [-3, -6, -9, -15, 2, 5, 8, 14],
+        [-3, -7, -10, -13, 2, 6, 9, 12],
+        [-2, -5, -8, -13, 1, 4, 7, 12],
+        [-2, -4, -6, -13, 1, 3, 5, 12],
+        [-3, -6, -8, -12, 2, 5, 7, 11],
+        [-3, -7, -9, -11, 2, 6, 8, 10],
+        [-4, -7, -8, -11, 3, 6, 7, 10],
+        [-3, -5, -8, -11, 2, 4, 7, 10],
+        [-2, -6, -8, -10, 1, 5, 7, 9],
+        [-2, -5, -8, -10, 1, 4, 7, 9],
+        [-2, -4, -8, -10, 1, 3, 7, 9],
+        [-2, -5, -7, -10, 1, 4, 6, 9],
+        [-3, -4, -7, -10, 2, 3, 6, 9],
+        [-1, -2, -3, -10, 0, 1, 2, 9],
+        [-4, -6, -8, -9, 3, 5, 7, 8],
+        [-3, -5, -7, -9, 2, 4, 6, 8],
+    ];
+    let blocks_x = width.div_ceil(4);
+    let mut alphas = vec![0; width * height];
+    for (index, block) in level.chunks_exact(16).enumerate() {
+        let base = i32::from(block[0]);
+        let multiplier = i32::from(block[1] >> 4);
+        let modifiers = MODIFIERS[usize::from(block[1] & 0xF)];
+        let indices = block[2..8]
+            .iter()
+            .fold(0u64, |indices, &byte| indices << 8 | u64::from(byte));
+        for pixel in 0..16 {
+            let (x, y) = (
+                index % blocks_x * 4 + pixel % 4,
+                index / blocks_x * 4 + pixel / 4,
+            );
+            if x < width && y < height {
+                let modifier =
+                    modifiers[(indices >> (45 - (pixel % 4 * 4 + pixel / 4) * 3) & 7) as usize];
+                alphas[y * width + x] = (base + modifier * multiplier).clamp(0, 255) as u8;
+            }
+        }
+    }
+    alphas
+}
+
+#[test]
+fn transcodes_etc1s_alpha_to_eac() {
+    let ktx2 = Ktx2::parse(
+        &fixture("etc1s_rgba.ktx2"),
+        wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+    )
+    .unwrap();
+    assert_eq!(ktx2.format, wgpu::TextureFormat::Etc2Rgba8UnormSrgb);
+    let reference = fixture("etc1s_rgba.rgba");
+    let mut reference = reference.chunks_exact(4).map(|rgba| rgba[3]);
+    for (level, data) in ktx2.levels.iter().enumerate() {
+        let size = ktx2
+            .size
+            .mip_level_size(level as u32, wgpu::TextureDimension::D2);
+        let (width, height) = (size.width as usize, size.height as usize);
+        for (pixel, alpha) in eac_alphas(data, width, height).into_iter().enumerate() {
+            let expected = reference.next().unwrap();
+            // EAC can't always hit the four alphas of an ETC1S block, the reference transcoder
+            // is up to 10 off on this texture.
+            assert!(
+                alpha.abs_diff(expected) <= 4,
+                "level {level} pixel {pixel}: {alpha} instead of {expected}"
+            );
+        }
+    }
+}
+
+/// Xorshift generator, so that the corruptions are the same on every run.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+#[test]
+fn rejects_corrupted_containers_without_panicking() {
+    let mut random = Random(0x9E37_79B9_7F4A_7C15);
+    let features = [
+        wgpu::Features::empty(),
+        wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+        wgpu::Features::TEXTURE_COMPRESSION_BC,
+        wgpu::Features::TEXTURE_COMPRESSION_ASTC,
+    ];
+    for name in ETC1S.iter().chain(&UASTC) {
+        let original = fixture(&format!("{name}.ktx2"));
+        for _ in 0..500 {
+            let mut bytes = original.clone();
+            match random.next(4) {
+                0 => bytes.truncate(random.next(bytes.len())),
+                1 => {
+                    let index = random.next(bytes.len());
+                    bytes[index] ^= 1 << random.next(8);
+                }
+                2 => {
+                    for _ in 0..8 {
+                        let index = random.next(bytes.len());
+                        bytes[index] = random.next(256) as u8;
+                    }
+                }
+                _ => {
+                    // Past the header and level index, where the payload is.
+                    let start = 80 + random.next(bytes.len() - 80);
+                    let len = random.next(bytes.len() - start);
+                    bytes[start..start + len].fill(random.next(256) as u8);
+                }
+            }
+            // Either result is fine, as long as parsing doesn't panic.
+            let _ = Ktx2::parse(&bytes, features[random.next(features.len())]);
+        }
+    }
+}